@@ -0,0 +1,403 @@
+use crate::fgrep::{GrepRequest, OutputFormat};
+use std::path::Path;
+
+/// File argument that reads the buffer from stdin
+pub const STDIN: &str = "-";
+
+/// Undocumented option the running instance adds to windows it opens for forwarded launches
+pub const FORWARDED: &str = "--forwarded";
+
+const USAGE: &str = "Usage:
+  editor [OPTIONS] [FILE[:LINE[:COLUMN]] | -]...
+  editor [OPTIONS] --grep <CONDITION> <DIRECTORY> [FILE TYPE] [GREP OPTIONS]
+
+Options:
+  -                     Read the buffer from stdin
+  --line <N>            Move the cursor to line N of files without a position
+  --column <N>          Move the cursor to column N of files without a position
+  --encoding <LABEL>    Open files, or grep, with this encoding instead of detecting it
+  --readonly            Open files read only
+  --wait                Return only after the window closes, with exit code 1 if changes were discarded.
+                        With -, print the buffer to stdout on close.
+  --new-window          Open a window of its own instead of handing the arguments to the running editor
+  -h, --help            Print help
+  -V, --version         Print version
+
+Grep options:
+  -g, --grep            Search CONDITION in files matching FILE TYPE (default *.*) under DIRECTORY
+  -m, --word            Match whole words only
+  -c, --case-sensitive  Match case
+  -r, --regexp          Treat CONDITION as a regular expression
+  -s, --recursive       Search subdirectories
+  -U, --multiline       Let matches span multiple lines
+  -P, --pcre2           Use the PCRE2 engine
+  --no-ignore           Search files excluded by .gitignore and .ignore
+  --hidden              Search hidden files and directories
+  --max-depth <N>       Descend at most N directories
+  --max-results <N>     Stop after N matching lines
+  --max-count <N>       Stop searching a file after N matching lines
+  --max-filesize <N>    Skip files larger than N bytes
+  --binary              Search files that look binary
+  -A <N>, -B <N>, -C <N>  Show N lines after, before or around each match
+  --headless            Print results to stdout instead of opening a window
+  --format <FORMAT>     Headless output format: plain, json or vimgrep";
+
+#[derive(Default)]
+pub struct CliArgs {
+    pub help: bool,
+    pub version: bool,
+    pub files: Vec<FileSpec>,
+    pub grep: Option<GrepRequest>,
+    pub encoding: Option<String>,
+    pub readonly: bool,
+    /// Block until the window closes, even when it is opened by the running instance
+    pub wait: bool,
+    pub new_window: bool,
+    /// Opened by the running instance for a later launch, so it behaves like the first window of that launch
+    pub forwarded: bool,
+    pub headless: Option<OutputFormat>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileSpec {
+    pub path: String,
+    pub line: Option<u64>,
+    pub column: Option<u64>,
+}
+
+impl CliArgs {
+    pub fn reads_stdin(&self) -> bool {
+        self.files.iter().any(|file| file.path == STDIN)
+    }
+}
+
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+pub fn version() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// Parses the process arguments, including the program name in args[0]
+pub fn parse(args: &[String]) -> Result<CliArgs, String> {
+    let mut cli = CliArgs::default();
+    let mut grep = GrepRequest::default();
+    let mut grep_given = false;
+    let mut grep_only: Option<&str> = None;
+    let mut line: Option<u64> = None;
+    let mut column: Option<u64> = None;
+    let mut headless = false;
+    let mut format: Option<OutputFormat> = None;
+    let mut context: Option<usize> = None;
+    let mut before: Option<usize> = None;
+    let mut after: Option<usize> = None;
+
+    let mut args = args.iter().skip(1).map(String::as_str).peekable();
+    while let Some(arg) = args.next() {
+        match arg {
+            "-h" | "--help" => {
+                cli.help = true;
+                return Ok(cli);
+            }
+            "-V" | "--version" => {
+                cli.version = true;
+                return Ok(cli);
+            }
+            "--line" => line = Some(position(arg, value(arg, args.next())?)?),
+            "--column" => column = Some(position(arg, value(arg, args.next())?)?),
+            "--encoding" => {
+                let label = value(arg, args.next())?;
+                encoding_rs::Encoding::for_label_no_replacement(label.as_bytes()).ok_or(format!("Unknown encoding: {}", label))?;
+                cli.encoding = Some(label.to_string());
+            }
+            "--readonly" => cli.readonly = true,
+            "--new-window" => cli.new_window = true,
+            "--wait" => cli.wait = true,
+            FORWARDED => cli.forwarded = true,
+            STDIN if cli.reads_stdin() => return Err("- can only be given once".to_string()),
+            "-g" | "--grep" => {
+                if grep_given {
+                    return Err(format!("{} can only be given once", arg));
+                }
+                grep_given = true;
+                let condition = value(arg, args.next())?;
+                let start_directory = args.next().ok_or(format!("{} requires a directory after the condition", arg))?;
+                let file_type = match args.peek() {
+                    Some(file_type) if !file_type.starts_with('-') => args.next().unwrap_or_default(),
+                    _ => "*.*",
+                };
+                grep.condition = condition.to_string();
+                grep.start_directory = start_directory.to_string();
+                grep.file_type = file_type.to_string();
+            }
+            /* Empty arguments come from callers that pass an optional path as is */
+            "" => {}
+            "--" => {
+                for path in args.by_ref() {
+                    cli.files.push(FileSpec {
+                        path: path.to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                grep_only.get_or_insert(arg);
+                match arg {
+                    "-m" | "--word" => grep.match_by_word = true,
+                    "-c" | "--case-sensitive" => grep.case_sensitive = true,
+                    "-r" | "--regexp" => grep.regexp = true,
+                    "-s" | "--recursive" => grep.recursive = true,
+                    "-U" | "--multiline" => grep.multiline = true,
+                    "-P" | "--pcre2" => grep.pcre2 = true,
+                    "--no-ignore" => grep.no_ignore = true,
+                    "--hidden" => grep.hidden = true,
+                    "--binary" => grep.search_binary = true,
+                    "--max-depth" => grep.max_depth = Some(number(arg, value(arg, args.next())?)?),
+                    "--max-results" => grep.max_results = Some(number(arg, value(arg, args.next())?)?),
+                    "--max-count" => grep.max_matches_per_file = Some(number(arg, value(arg, args.next())?)?),
+                    "--max-filesize" => grep.max_filesize = Some(number(arg, value(arg, args.next())?)?),
+                    "-A" => after = Some(number(arg, value(arg, args.next())?)?),
+                    "-B" => before = Some(number(arg, value(arg, args.next())?)?),
+                    "-C" => context = Some(number(arg, value(arg, args.next())?)?),
+                    "--headless" => headless = true,
+                    "--format" => {
+                        format = Some(match value(arg, args.next())? {
+                            "plain" => OutputFormat::Plain,
+                            "json" => OutputFormat::Json,
+                            "vimgrep" => OutputFormat::Vimgrep,
+                            other => return Err(format!("Unknown format: {} (expected plain, json or vimgrep)", other)),
+                        })
+                    }
+                    _ => return Err(format!("Unknown option: {}", arg)),
+                }
+            }
+            _ => cli.files.push(file_spec(arg)?),
+        }
+    }
+
+    if grep_given {
+        if !cli.files.is_empty() {
+            return Err("Files cannot be opened together with --grep".to_string());
+        }
+        if line.is_some() || column.is_some() {
+            return Err("--line and --column cannot be used with --grep".to_string());
+        }
+        if format.is_some() && !headless {
+            return Err("--format requires --headless".to_string());
+        }
+        grep.encoding = cli.encoding.take();
+        grep.before_context = before.or(context).unwrap_or_default();
+        grep.after_context = after.or(context).unwrap_or_default();
+        if headless {
+            cli.headless = Some(format.unwrap_or(OutputFormat::Plain));
+        }
+        cli.grep = Some(grep);
+    } else if let Some(option) = grep_only {
+        return Err(format!("{} requires --grep", option));
+    }
+
+    if line.is_some() || column.is_some() {
+        if cli.files.is_empty() {
+            return Err("--line and --column require a file".to_string());
+        }
+        for file in cli.files.iter_mut().filter(|file| file.line.is_none()) {
+            file.line = Some(line.unwrap_or(1));
+            file.column = column;
+        }
+    }
+
+    Ok(cli)
+}
+
+/// Splits `file:line:column` or `file:line`, stopping at the longest name that exists as a file
+fn file_spec(arg: &str) -> Result<FileSpec, String> {
+    let mut file = FileSpec {
+        path: arg.to_string(),
+        ..Default::default()
+    };
+    if arg == STDIN {
+        return Ok(file);
+    }
+
+    let mut path = arg;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 && !Path::new(path).exists() {
+        match path.rsplit_once(':') {
+            Some((head, tail)) if !head.is_empty() && !tail.is_empty() && tail.bytes().all(|b| b.is_ascii_digit()) => {
+                numbers.insert(0, tail);
+                path = head;
+            }
+            _ => break,
+        }
+    }
+
+    if let Some(line) = numbers.first() {
+        file.path = path.to_string();
+        file.line = Some(position(arg, line)?);
+        file.column = numbers.get(1).map(|column| position(arg, column)).transpose()?;
+    }
+
+    Ok(file)
+}
+
+fn value<'a>(name: &str, value: Option<&'a str>) -> Result<&'a str, String> {
+    value.ok_or(format!("{} requires a value", name))
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", name, value))
+}
+
+fn position(name: &str, value: &str) -> Result<u64, String> {
+    match number(name, value)? {
+        0 => Err(format!("{} expects a number starting at 1, got '{}'", name, value)),
+        position => Ok(position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<CliArgs, String> {
+        let args: Vec<String> = std::iter::once("editor").chain(args.iter().copied()).map(String::from).collect();
+        parse(&args)
+    }
+
+    fn spec(path: &str, line: Option<u64>, column: Option<u64>) -> FileSpec {
+        FileSpec {
+            path: path.to_string(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn splits_line_and_column() {
+        let cli = parse_args(&["a.txt", "b.txt:10", "c.txt:10:5"]).unwrap();
+        assert_eq!(cli.files, vec![spec("a.txt", None, None), spec("b.txt", Some(10), None), spec("c.txt", Some(10), Some(5))]);
+    }
+
+    #[test]
+    fn keeps_windows_drive() {
+        let cli = parse_args(&[r"C:\x:10", r"C:\x:10:5", r"C:\x"]).unwrap();
+        assert_eq!(cli.files, vec![spec(r"C:\x", Some(10), None), spec(r"C:\x", Some(10), Some(5)), spec(r"C:\x", None, None)]);
+    }
+
+    #[test]
+    fn keeps_existing_file_with_position_in_name() {
+        let dir = std::env::temp_dir().join(format!("editor-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("log:12");
+        std::fs::write(&file, "").unwrap();
+        let path = file.to_string_lossy().to_string();
+
+        let exact = parse_args(&[&path]).map(|cli| cli.files);
+        let with_line = parse_args(&[&format!("{}:3", path)]).map(|cli| cli.files);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(exact.unwrap(), vec![spec(&path, None, None)]);
+        assert_eq!(with_line.unwrap(), vec![spec(&path, Some(3), None)]);
+    }
+
+    #[test]
+    fn rejects_zero_position_in_file_spec() {
+        assert!(parse_args(&["a.txt:0"]).is_err());
+        assert!(parse_args(&["a.txt:1:0"]).is_err());
+    }
+
+    #[test]
+    fn applies_line_and_column_to_files_without_position() {
+        let cli = parse_args(&["--line", "3", "--column", "4", "a.txt", "b.txt:7"]).unwrap();
+        assert_eq!(cli.files, vec![spec("a.txt", Some(3), Some(4)), spec("b.txt", Some(7), None)]);
+
+        let cli = parse_args(&["--column", "4", "a.txt"]).unwrap();
+        assert_eq!(cli.files, vec![spec("a.txt", Some(1), Some(4))]);
+    }
+
+    #[test]
+    fn rejects_invalid_line_and_column() {
+        assert!(parse_args(&["--line", "0", "a.txt"]).is_err());
+        assert!(parse_args(&["--column", "0", "a.txt"]).is_err());
+        assert!(parse_args(&["--line", "x", "a.txt"]).is_err());
+        assert!(parse_args(&["--column", "-1", "a.txt"]).is_err());
+        assert!(parse_args(&["a.txt", "--line"]).is_err());
+        assert!(parse_args(&["--line", "3"]).is_err());
+    }
+
+    #[test]
+    fn reads_stdin_once() {
+        let cli = parse_args(&["-", "a.txt"]).unwrap();
+        assert!(cli.reads_stdin());
+        assert_eq!(parse_args(&["-", "-"]).err().unwrap(), "- can only be given once");
+    }
+
+    #[test]
+    fn parses_grep_with_file_type() {
+        let cli = parse_args(&["--grep", "foo", "src", "*.rs", "-r", "-C", "2"]).unwrap();
+        let grep = cli.grep.unwrap();
+        assert_eq!(grep.condition, "foo");
+        assert_eq!(grep.start_directory, "src");
+        assert_eq!(grep.file_type, "*.rs");
+        assert!(grep.regexp);
+        assert_eq!((grep.before_context, grep.after_context), (2, 2));
+        assert!(cli.files.is_empty());
+    }
+
+    #[test]
+    fn parses_grep_without_file_type() {
+        let grep = parse_args(&["-g", "foo", "src", "-s"]).unwrap().grep.unwrap();
+        assert_eq!(grep.file_type, "*.*");
+        assert!(grep.recursive);
+
+        let grep = parse_args(&["-g", "foo", "src"]).unwrap().grep.unwrap();
+        assert_eq!(grep.file_type, "*.*");
+    }
+
+    #[test]
+    fn rejects_incomplete_grep() {
+        assert!(parse_args(&["--grep"]).is_err());
+        assert!(parse_args(&["--grep", "foo"]).is_err());
+        assert!(parse_args(&["--grep", "foo", "src", "--grep", "bar", "src"]).is_err());
+        assert!(parse_args(&["--grep", "foo", "src", "*.rs", "a.txt"]).is_err());
+    }
+
+    #[test]
+    fn rejects_grep_options_without_grep() {
+        assert_eq!(parse_args(&["-r", "a.txt"]).err().unwrap(), "-r requires --grep");
+        assert_eq!(parse_args(&["--max-results", "5"]).err().unwrap(), "--max-results requires --grep");
+        assert_eq!(parse_args(&["--headless"]).err().unwrap(), "--headless requires --grep");
+    }
+
+    #[test]
+    fn requires_headless_for_format() {
+        assert_eq!(parse_args(&["-g", "foo", "src", "--format", "json"]).err().unwrap(), "--format requires --headless");
+
+        let cli = parse_args(&["-g", "foo", "src", "--headless", "--format", "vimgrep"]).unwrap();
+        assert!(matches!(cli.headless, Some(OutputFormat::Vimgrep)));
+
+        let cli = parse_args(&["-g", "foo", "src", "--headless"]).unwrap();
+        assert!(matches!(cli.headless, Some(OutputFormat::Plain)));
+
+        assert!(parse_args(&["-g", "foo", "src", "--headless", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn treats_arguments_after_double_dash_as_files() {
+        let cli = parse_args(&["--readonly", "--", "--line", "-r", "a.txt:3"]).unwrap();
+        assert!(cli.readonly);
+        assert_eq!(cli.files, vec![spec("--line", None, None), spec("-r", None, None), spec("a.txt:3", None, None)]);
+    }
+
+    #[test]
+    fn accepts_forwarded_without_grep() {
+        let cli = parse_args(&[FORWARDED, "--new-window", "a.txt"]).unwrap();
+        assert!(cli.forwarded && cli.new_window);
+    }
+
+    #[test]
+    fn ignores_empty_arguments() {
+        assert!(parse_args(&[""]).unwrap().files.is_empty());
+    }
+}
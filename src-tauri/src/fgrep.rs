@@ -1,132 +1,1005 @@
-use globset::Glob;
+use crate::helper;
+use crossbeam_channel::unbounded;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use grep::{
-    matcher::Matcher,
+    matcher::{Captures, Matcher},
     regex::{RegexMatcher, RegexMatcherBuilder},
-    searcher::{sinks::Lossy, MmapChoice, SearcherBuilder},
+    searcher::{BinaryDetection, Encoding, Searcher, SearcherBuilder, Sink, SinkContext, SinkContextKind, SinkMatch},
 };
+use ignore::WalkBuilder;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
 use tauri::{Emitter, EventTarget};
-use zouni::Dirent;
 
-static CANCEL: Mutex<bool> = Mutex::new(false);
+static JOBS: Mutex<BTreeMap<u32, GrepJob>> = Mutex::new(BTreeMap::new());
+static NEXT_JOB_ID: AtomicU32 = AtomicU32::new(1);
 const GREP_EVENT_NAME: &str = "grep_progress";
+const GREP_RESULTS_EVENT_NAME: &str = "grep_results";
+const GREP_COMPLETE_EVENT_NAME: &str = "grep_complete";
+const BATCH_SIZE: usize = 500;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GrepProgress {
+    job_id: u32,
     processing: String,
     current: usize,
     total: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GrepRequest {
     pub condition: String,
     pub start_directory: String,
+    /// Semicolon separated globs such as `*.rs;*.toml;!**/generated/**`. Patterns starting with `!` exclude files.
     pub file_type: String,
     pub match_by_word: bool,
     pub case_sensitive: bool,
     pub regexp: bool,
     pub recursive: bool,
+    #[serde(default)]
+    pub no_ignore: bool,
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub multiline: bool,
+    /// Use the PCRE2 engine for lookaround and backreferences. Requires the `pcre2` feature.
+    #[serde(default)]
+    pub pcre2: bool,
+    #[serde(default)]
+    pub encoding: Option<String>,
+    #[serde(default)]
+    pub max_results: Option<usize>,
+    #[serde(default)]
+    pub max_matches_per_file: Option<usize>,
+    #[serde(default)]
+    pub max_filesize: Option<u64>,
+    /// Search files that look binary instead of skipping them at the first NUL byte
+    #[serde(default)]
+    pub search_binary: bool,
+    #[serde(default)]
+    pub before_context: usize,
+    #[serde(default)]
+    pub after_context: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GrepResult {
     full_path: String,
+    encoding: String,
     line_number: u64,
     line: String,
     ranges: Vec<(usize, usize)>,
+    /// UTF-16 code unit offsets of each match in line, as used for editor columns
+    columns: Vec<(usize, usize)>,
+    spans: Vec<GrepSpan>,
+    before: Vec<GrepContextLine>,
+    after: Vec<GrepContextLine>,
 }
 
-pub async fn run_grep(window: &tauri::WebviewWindow, e: GrepRequest) -> Result<Vec<GrepResult>, String> {
-    {
-        let mut token = CANCEL.lock().unwrap();
-        *token = false;
+/// Start and end of a match as (line number, column) pairs, so matches spanning lines can be located.
+/// Columns are 0-based, in bytes for start/end and in UTF-16 code units for start_utf16/end_utf16.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GrepSpan {
+    start: (u64, usize),
+    end: (u64, usize),
+    start_utf16: (u64, usize),
+    end_utf16: (u64, usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GrepContextLine {
+    line_number: u64,
+    line: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GrepBatch {
+    job_id: u32,
+    results: Vec<GrepResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GrepSummary {
+    job_id: u32,
+    total: usize,
+    searched: usize,
+    matches: usize,
+    cancelled: bool,
+    /// More lines than max_results matched and the search stopped early
+    truncated: bool,
+    truncated_files: Vec<String>,
+    skipped: Vec<SkippedFile>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    TooLarge,
+    Binary,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedFile {
+    full_path: String,
+    reason: SkipReason,
+}
+
+#[derive(Default)]
+struct FileOutcome {
+    results: Vec<GrepResult>,
+    truncated: bool,
+    skipped: Option<SkipReason>,
+}
+
+struct GrepJob {
+    label: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Results are sent to the window as they are found, followed by a summary when the job ends.
+pub fn run_grep(window: &tauri::WebviewWindow, e: GrepRequest) -> Result<u32, String> {
+    if e.pcre2 {
+        #[cfg(feature = "pcre2")]
+        return start_job(window, e.clone(), build_pcre2_matcher(&e)?);
+        #[cfg(not(feature = "pcre2"))]
+        return Err("PCRE2 is not available in this build".to_string());
     }
-    let items = zouni::fs::readdir(e.start_directory, e.recursive, false)?;
-    let glob = Glob::new(&e.file_type).map_err(|e| e.to_string())?.compile_matcher();
-    let files: Vec<Dirent> = items
-        .into_iter()
-        .filter(|item| !item.attributes.is_symbolic_link && !item.attributes.is_directory && !item.attributes.is_device && !item.attributes.is_system)
-        .filter(|item| {
-            if e.file_type == "*.*" {
-                true
-            } else {
-                glob.is_match(&item.name)
+
+    let matcher = build_matcher(&e)?;
+    start_job(window, e, matcher)
+}
+
+fn start_job<M>(window: &tauri::WebviewWindow, e: GrepRequest, matcher: M) -> Result<u32, String>
+where
+    M: Matcher + Clone + Send + 'static,
+    M::Error: std::fmt::Display,
+{
+    build_file_filter(&e.file_type)?;
+    resolve_encoding(&e)?;
+
+    let job_id = NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    JOBS.lock().unwrap().insert(
+        job_id,
+        GrepJob {
+            label: window.label().to_string(),
+            cancelled: cancelled.clone(),
+        },
+    );
+
+    let window = window.clone();
+    thread::spawn(move || {
+        let summary = match list_files(&e) {
+            Ok(files) => search_files(
+                &e,
+                files,
+                matcher,
+                job_id,
+                &cancelled,
+                |progress| emit(&window, GREP_EVENT_NAME, progress),
+                |results| {
+                    emit(
+                        &window,
+                        GREP_RESULTS_EVENT_NAME,
+                        GrepBatch {
+                            job_id,
+                            results,
+                        },
+                    )
+                },
+            ),
+            Err(error) => GrepSummary {
+                job_id,
+                error: Some(error),
+                ..Default::default()
+            },
+        };
+        JOBS.lock().unwrap().remove(&job_id);
+        emit(&window, GREP_COMPLETE_EVENT_NAME, summary);
+    });
+
+    Ok(job_id)
+}
+
+pub enum OutputFormat {
+    Plain,
+    Json,
+    Vimgrep,
+}
+
+/// Runs grep without a window and writes results to stdout.
+/// Returns 0 when something matched, 1 when nothing matched and 2 on error, like grep.
+pub fn run_headless(e: GrepRequest, format: OutputFormat) -> i32 {
+    let searched = if e.pcre2 {
+        #[cfg(feature = "pcre2")]
+        {
+            build_pcre2_matcher(&e).and_then(|matcher| search_headless(&e, matcher, format))
+        }
+        #[cfg(not(feature = "pcre2"))]
+        Err("PCRE2 is not available in this build".to_string())
+    } else {
+        build_matcher(&e).and_then(|matcher| search_headless(&e, matcher, format))
+    };
+
+    let summary = match searched {
+        Ok(summary) => summary,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+    };
+
+    if summary.truncated {
+        eprintln!("Stopped at {} matches", summary.matches);
+    }
+    for full_path in &summary.truncated_files {
+        eprintln!("{}: stopped at {} matches", full_path, e.max_matches_per_file.unwrap_or_default());
+    }
+    for skipped in &summary.skipped {
+        eprintln!("{}: skipped ({:?})", skipped.full_path, skipped.reason);
+    }
+
+    if summary.skipped.iter().any(|skipped| matches!(skipped.reason, SkipReason::Error)) {
+        2
+    } else if summary.matches > 0 {
+        0
+    } else {
+        1
+    }
+}
+
+fn search_headless<M>(e: &GrepRequest, matcher: M, format: OutputFormat) -> Result<GrepSummary, String>
+where
+    M: Matcher + Clone + Send,
+    M::Error: std::fmt::Display,
+{
+    build_file_filter(&e.file_type)?;
+    resolve_encoding(e)?;
+    let files = list_files(e)?;
+
+    let cancelled = AtomicBool::new(false);
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let summary = search_files(
+        e,
+        files,
+        matcher,
+        0,
+        &cancelled,
+        |_| {},
+        |results| {
+            // Stop searching once the reader of stdout has gone away
+            if write_results(&mut out, &results, &format).is_err() {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+        },
+    );
+    let _ = out.flush();
+
+    Ok(summary)
+}
+
+/// Multiline matches are printed one line per record, so text after a line break would start a bogus record
+fn write_results<W: Write>(out: &mut W, results: &[GrepResult], format: &OutputFormat) -> io::Result<()> {
+    for result in results {
+        match format {
+            OutputFormat::Plain => {
+                for context in &result.before {
+                    writeln!(out, "{}-{}-{}", result.full_path, context.line_number, nth_line(&context.line, 0))?;
+                }
+                writeln!(out, "{}:{}:{}:{}", result.full_path, result.line_number, result.ranges[0].0 + 1, nth_line(&result.line, 0))?;
+                for context in &result.after {
+                    writeln!(out, "{}-{}-{}", result.full_path, context.line_number, nth_line(&context.line, 0))?;
+                }
+            }
+            OutputFormat::Json => {
+                writeln!(out, "{}", serde_json::to_string(result).map_err(io::Error::other)?)?;
+            }
+            OutputFormat::Vimgrep => {
+                for span in &result.spans {
+                    writeln!(out, "{}:{}:{}:{}", result.full_path, span.start.0, span.start.1 + 1, nth_line(&result.line, (span.start.0 - result.line_number) as usize))?;
+                }
             }
+        }
+    }
+    out.flush()
+}
+
+fn nth_line(text: &str, n: usize) -> &str {
+    text.lines().nth(n).unwrap_or_default()
+}
+
+fn resolve_encoding(e: &GrepRequest) -> Result<Option<&'static encoding_rs::Encoding>, String> {
+    match &e.encoding {
+        Some(label) => encoding_rs::Encoding::for_label_no_replacement(label.as_bytes()).map(Some).ok_or(format!("Unknown encoding: {}", label)),
+        None => Ok(None),
+    }
+}
+
+/// Files to search under the start directory, sorted by path
+fn list_files(e: &GrepRequest) -> Result<Vec<String>, String> {
+    let (include, exclude) = build_file_filter(&e.file_type)?;
+    let mut files: Vec<String> = walk(e)?
+        .into_iter()
+        .filter(|entry| {
+            let relative = entry.path().strip_prefix(&e.start_directory).unwrap_or(entry.path());
+            (include.is_empty() || include.is_match(relative)) && !exclude.is_match(relative)
         })
+        .map(|entry| entry.path().to_string_lossy().to_string())
         .collect();
+    files.sort();
+    Ok(files)
+}
 
-    if files.is_empty() {
-        return Ok(Vec::new());
-    };
-    let total = files.len();
-
+fn build_matcher(e: &GrepRequest) -> Result<RegexMatcher, String> {
     let mut builder = RegexMatcherBuilder::new();
     let builder = builder.case_insensitive(!e.case_sensitive);
     let builder = builder.word(e.match_by_word);
     let builder = builder.fixed_strings(!e.regexp);
-    let matcher: RegexMatcher = builder.build(&e.condition).unwrap();
-    let mut searcher = SearcherBuilder::new().memory_map(unsafe { MmapChoice::auto() }).build();
-
-    let mut results: HashMap<String, GrepResult> = HashMap::new();
-    for (count, file) in files.iter().enumerate() {
-        if let Ok(token) = CANCEL.try_lock() {
-            if *token {
-                break;
+    let builder = builder.multi_line(e.multiline);
+    builder.build(&e.condition).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "pcre2")]
+fn build_pcre2_matcher(e: &GrepRequest) -> Result<grep::pcre2::RegexMatcher, String> {
+    let mut builder = grep::pcre2::RegexMatcherBuilder::new();
+    let builder = builder.caseless(!e.case_sensitive);
+    let builder = builder.word(e.match_by_word);
+    let builder = builder.fixed_strings(!e.regexp);
+    let builder = builder.multi_line(e.multiline);
+    let builder = builder.utf(true).ucp(true).jit_if_available(true);
+    builder.build(&e.condition).map_err(|e| e.to_string())
+}
+
+/// Searches files on a worker pool. Progress is reported as each file finishes and results are passed on in file order.
+fn search_files<M, P, R>(e: &GrepRequest, files: Vec<String>, matcher: M, job_id: u32, cancelled: &AtomicBool, mut on_progress: P, mut on_results: R) -> GrepSummary
+where
+    M: Matcher + Clone + Send,
+    M::Error: std::fmt::Display,
+    P: FnMut(GrepProgress),
+    R: FnMut(Vec<GrepResult>),
+{
+    let forced_encoding = resolve_encoding(e).ok().flatten();
+    let total = files.len();
+    let workers = thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1).min(total);
+    let (file_sender, file_receiver) = unbounded::<usize>();
+    let (result_sender, result_receiver) = unbounded::<(usize, FileOutcome)>();
+    for index in 0..total {
+        file_sender.send(index).unwrap();
+    }
+    drop(file_sender);
+
+    let mut summary = GrepSummary {
+        job_id,
+        total,
+        ..Default::default()
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let file_receiver = file_receiver.clone();
+            let result_sender = result_sender.clone();
+            let matcher = matcher.clone();
+            let files = &files;
+            scope.spawn(move || {
+                let mut builder = SearcherBuilder::new();
+                builder.multi_line(e.multiline).before_context(e.before_context).after_context(e.after_context);
+                builder.binary_detection(if e.search_binary {
+                    BinaryDetection::none()
+                } else {
+                    BinaryDetection::quit(b'\x00')
+                });
+                while let Ok(index) = file_receiver.recv() {
+                    if cancelled.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let outcome = search_file(&mut builder, &matcher, e, &files[index], forced_encoding, cancelled);
+                    if result_sender.send((index, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+
+        // Workers finish out of order, so hold results back until every file before them is done
+        let mut pending: BTreeMap<usize, FileOutcome> = BTreeMap::new();
+        let mut next_index = 0;
+        let mut batch: Vec<GrepResult> = Vec::new();
+        while let Ok((index, outcome)) = result_receiver.recv() {
+            summary.searched += 1;
+            on_progress(GrepProgress {
+                job_id,
+                processing: files[index].clone(),
+                current: summary.searched,
+                total,
+            });
+
+            pending.insert(index, outcome);
+            while let Some(mut outcome) = pending.remove(&next_index) {
+                let full_path = &files[next_index];
+                next_index += 1;
+                if summary.truncated {
+                    continue;
+                }
+
+                if let Some(reason) = outcome.skipped {
+                    summary.skipped.push(SkippedFile {
+                        full_path: full_path.clone(),
+                        reason,
+                    });
+                }
+                if outcome.truncated {
+                    summary.truncated_files.push(full_path.clone());
+                }
+
+                if let Some(max_results) = e.max_results {
+                    // Reaching the limit exactly drops nothing, so only going past it counts as truncated
+                    if summary.matches + outcome.results.len() > max_results {
+                        outcome.results.truncate(max_results - summary.matches);
+                        summary.truncated = true;
+                        // Nothing more is needed from the workers
+                        cancelled.store(true, Ordering::Relaxed);
+                    }
+                }
+                summary.matches += outcome.results.len();
+                batch.extend(outcome.results);
+            }
+
+            // Results are sent as soon as they are in order, split so no event carries more than BATCH_SIZE
+            while !batch.is_empty() {
+                let rest = batch.split_off(batch.len().min(BATCH_SIZE));
+                on_results(std::mem::replace(&mut batch, rest));
             }
         }
-        window
-            .emit_to(
-                EventTarget::WebviewWindow {
-                    label: window.label().to_string(),
-                },
-                GREP_EVENT_NAME,
-                GrepProgress {
-                    processing: file.full_path.clone(),
-                    current: count + 1,
-                    total,
-                },
-            )
-            .unwrap();
-
-        searcher
-            .search_path(
-                &matcher,
-                &file.full_path,
-                Lossy(|line_number, line| {
-                    matcher.find_iter(line.as_bytes(), |matched| {
-                        let full_path = file.full_path.clone();
-                        let mut key = full_path.clone();
-                        key.push_str(&line_number.to_string());
-                        if let Some(result) = results.get_mut(&key) {
-                            result.ranges.push((matched.start(), matched.end()));
-                        } else {
-                            let result = GrepResult {
-                                full_path,
-                                line_number,
-                                line: line.to_string(),
-                                ranges: vec![(matched.start(), matched.end())],
-                            };
-                            results.insert(key, result);
-                        }
-                        true
-                    })?;
-
-                    Ok(true)
-                }),
-            )
-            .map_err(|e| e.to_string())?;
-    }
-
-    Ok(results.into_values().collect())
-}
-
-pub fn cancel() {
-    loop {
-        if let Ok(mut token) = CANCEL.try_lock() {
-            *token = true;
-            break;
+    });
+
+    summary.cancelled = cancelled.load(Ordering::Relaxed) && !summary.truncated;
+
+    summary
+}
+
+fn search_file<M>(builder: &mut SearcherBuilder, matcher: &M, e: &GrepRequest, full_path: &str, forced_encoding: Option<&'static encoding_rs::Encoding>, cancelled: &AtomicBool) -> FileOutcome
+where
+    M: Matcher,
+    M::Error: std::fmt::Display,
+{
+    let skipped = |reason: SkipReason| FileOutcome {
+        skipped: Some(reason),
+        ..Default::default()
+    };
+
+    let file = match std::fs::File::open(full_path) {
+        Ok(file) => file,
+        Err(ex) => {
+            eprintln!("[FS_ERR] Failed to open {}: {:?}", full_path, ex);
+            return skipped(SkipReason::Error);
         }
+    };
+
+    if let Some(max_filesize) = e.max_filesize {
+        if file.metadata().map(|metadata| metadata.len() > max_filesize).unwrap_or(false) {
+            return skipped(SkipReason::TooLarge);
+        }
+    }
+
+    let encoding = forced_encoding.unwrap_or_else(|| detect_encoding(full_path));
+    // UTF-8 is searched as is. Anything else is transcoded to UTF-8 before matching.
+    let transcode = if encoding == encoding_rs::UTF_8 {
+        None
+    } else {
+        Some(Encoding::new(encoding.name()).unwrap())
+    };
+    let mut searcher = builder.encoding(transcode).build();
+    let mut sink = ResultSink::new(matcher, full_path, encoding.name(), e.max_matches_per_file, cancelled);
+    // Files are read rather than memory mapped so a cancelled job can stop in the middle of a file
+    let searched = searcher.search_reader(
+        matcher,
+        CancellableReader {
+            inner: file,
+            cancelled,
+        },
+        &mut sink,
+    );
+
+    if let Err(ex) = searched {
+        eprintln!("[FS_ERR] Failed to search {}: {:?}", full_path, ex);
+        return skipped(SkipReason::Error);
+    }
+
+    if sink.binary {
+        return skipped(SkipReason::Binary);
+    }
+
+    FileOutcome {
+        truncated: sink.truncated,
+        results: sink.results,
+        skipped: None,
+    }
+}
+
+/// Reader that reports the end of file once the job is cancelled
+struct CancellableReader<'a, R> {
+    inner: R,
+    cancelled: &'a AtomicBool,
+}
+
+impl<R: io::Read> io::Read for CancellableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Ok(0);
+        }
+        self.inner.read(buf)
+    }
+}
+
+struct ResultSink<'a, M> {
+    matcher: &'a M,
+    full_path: &'a str,
+    encoding: &'static str,
+    max_matches: Option<usize>,
+    cancelled: &'a AtomicBool,
+    before: Vec<GrepContextLine>,
+    results: Vec<GrepResult>,
+    truncated: bool,
+    binary: bool,
+}
+
+impl<'a, M: Matcher> ResultSink<'a, M> {
+    fn new(matcher: &'a M, full_path: &'a str, encoding: &'static str, max_matches: Option<usize>, cancelled: &'a AtomicBool) -> Self {
+        Self {
+            matcher,
+            full_path,
+            encoding,
+            max_matches,
+            cancelled,
+            before: Vec::new(),
+            results: Vec::new(),
+            truncated: false,
+            binary: false,
+        }
+    }
+}
+
+impl<M> Sink for ResultSink<'_, M>
+where
+    M: Matcher,
+    M::Error: std::fmt::Display,
+{
+    type Error = io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
+        // Returning false stops the search of this file, so cancelling a huge file takes effect at once
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        let line = String::from_utf8_lossy(mat.bytes()).to_string();
+        let line_number = mat.line_number().unwrap_or_default();
+        let mut ranges = Vec::new();
+        self.matcher
+            .find_iter(line.as_bytes(), |matched| {
+                ranges.push((matched.start(), matched.end()));
+                true
+            })
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        if !ranges.is_empty() {
+            // Only a match past the limit means the file was cut short
+            if self.max_matches.is_some_and(|max_matches| self.results.len() >= max_matches) {
+                self.truncated = true;
+                return Ok(false);
+            }
+
+            let columns = ranges.iter().map(|(start, end)| (utf16_length(&line, 0, *start), utf16_length(&line, 0, *end))).collect();
+            let spans = ranges
+                .iter()
+                .map(|(start, end)| {
+                    let start = to_line_column(&line, line_number, *start);
+                    let end = to_line_column(&line, line_number, *end);
+                    GrepSpan {
+                        start: (start.0, start.1),
+                        end: (end.0, end.1),
+                        start_utf16: (start.0, start.2),
+                        end_utf16: (end.0, end.2),
+                    }
+                })
+                .collect();
+            self.results.push(GrepResult {
+                full_path: self.full_path.to_string(),
+                encoding: self.encoding.to_string(),
+                line_number,
+                line,
+                ranges,
+                columns,
+                spans,
+                before: std::mem::take(&mut self.before),
+                after: Vec::new(),
+            });
+        } else {
+            // No result for this line, so its before context must not end up on the next one
+            self.before.clear();
+        }
+
+        Ok(true)
+    }
+
+    fn binary_data(&mut self, _searcher: &Searcher, _binary_byte_offset: u64) -> Result<bool, io::Error> {
+        self.binary = true;
+        Ok(false)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, io::Error> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        let line = GrepContextLine {
+            line_number: context.line_number().unwrap_or_default(),
+            line: String::from_utf8_lossy(context.bytes()).to_string(),
+        };
+
+        // Lines between two matches are reported once, as after context of the first or before context of the next
+        match context.kind() {
+            SinkContextKind::Before => self.before.push(line),
+            SinkContextKind::After => {
+                if let Some(result) = self.results.last_mut() {
+                    result.after.push(line);
+                }
+            }
+            SinkContextKind::Other => {}
+        }
+
+        Ok(true)
+    }
+}
+
+fn detect_encoding(full_path: &str) -> &'static encoding_rs::Encoding {
+    let Ok(mut file) = std::fs::File::open(full_path) else {
+        return encoding_rs::UTF_8;
+    };
+    let size = file.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+    helper::detect_file_encoding(&mut file, size).map(|(encoding, _)| encoding).unwrap_or(encoding_rs::UTF_8)
+}
+
+fn to_line_column(text: &str, line_number: u64, offset: usize) -> (u64, usize, usize) {
+    let head = &text.as_bytes()[..offset];
+    let lines = memchr::memchr_iter(b'\n', head).count() as u64;
+    let line_start = memchr::memrchr(b'\n', head).map(|index| index + 1).unwrap_or(0);
+    (line_number + lines, offset - line_start, utf16_length(text, line_start, offset))
+}
+
+fn utf16_length(text: &str, start: usize, end: usize) -> usize {
+    match text.get(start..end) {
+        Some(slice) => slice.encode_utf16().count(),
+        // Not on a char boundary, which only happens with byte oriented patterns
+        None => String::from_utf8_lossy(&text.as_bytes()[start..end]).encode_utf16().count(),
+    }
+}
+
+/// Splits the file type list into include and exclude sets. `*.*` is kept as an alias of all files.
+fn build_file_filter(file_type: &str) -> Result<(GlobSet, GlobSet), String> {
+    let mut include = GlobSetBuilder::new();
+    let mut exclude = GlobSetBuilder::new();
+    for pattern in file_type.split(';').map(str::trim).filter(|pattern| !pattern.is_empty() && *pattern != "*.*") {
+        if let Some(pattern) = pattern.strip_prefix('!') {
+            exclude.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+        } else {
+            include.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+        }
+    }
+
+    Ok((include.build().map_err(|e| e.to_string())?, exclude.build().map_err(|e| e.to_string())?))
+}
+
+fn walk(e: &GrepRequest) -> Result<Vec<ignore::DirEntry>, String> {
+    if !Path::new(&e.start_directory).is_dir() {
+        return Err(format!("{} is not a directory", e.start_directory));
+    }
+
+    let max_depth = if e.recursive {
+        e.max_depth
+    } else {
+        Some(1)
+    };
+
+    let mut entries = Vec::new();
+    for entry in WalkBuilder::new(&e.start_directory)
+        .hidden(!e.hidden)
+        .parents(!e.no_ignore)
+        .ignore(!e.no_ignore)
+        .git_ignore(!e.no_ignore)
+        .git_global(!e.no_ignore)
+        .git_exclude(!e.no_ignore)
+        .max_depth(max_depth)
+        .follow_links(false)
+        .build()
+    {
+        match entry {
+            Ok(entry) => {
+                if entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                    entries.push(entry);
+                }
+            }
+            Err(e) => eprintln!("[FS_ERR] Failed to read entry: {:?}", e),
+        }
+    }
+
+    Ok(entries)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceRequest {
+    pub grep: GrepRequest,
+    /// Replacement text. `$1` or `${name}` refer to capture groups when `regexp` is set.
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceChange {
+    line_number: u64,
+    before: String,
+    after: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacePreview {
+    full_path: String,
+    encoding: String,
+    hash: String,
+    changes: Vec<ReplaceChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceSelection {
+    pub full_path: String,
+    pub hash: String,
+    pub line_numbers: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyReplaceRequest {
+    pub request: ReplaceRequest,
+    pub files: Vec<ReplaceSelection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaceStatus {
+    Replaced,
+    /// The file changed after the preview and was left untouched
+    Conflict,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceOutcome {
+    full_path: String,
+    status: ReplaceStatus,
+    replaced: usize,
+    message: Option<String>,
+}
+
+pub fn run_replace(e: ReplaceRequest) -> Result<Vec<ReplacePreview>, String> {
+    if e.grep.multiline {
+        return Err("Multiline replace is not supported".to_string());
+    }
+
+    let forced_encoding = resolve_encoding(&e.grep)?;
+    let files = list_files(&e.grep)?;
+
+    if e.grep.pcre2 {
+        #[cfg(feature = "pcre2")]
+        return Ok(preview_replace(&e, files, build_pcre2_matcher(&e.grep)?, forced_encoding));
+        #[cfg(not(feature = "pcre2"))]
+        return Err("PCRE2 is not available in this build".to_string());
+    }
+
+    Ok(preview_replace(&e, files, build_matcher(&e.grep)?, forced_encoding))
+}
+
+/// Writes the selected lines of each previewed file, keeping its encoding, BOM and line endings
+pub fn apply_replace(e: ApplyReplaceRequest) -> Result<Vec<ReplaceOutcome>, String> {
+    let forced_encoding = resolve_encoding(&e.request.grep)?;
+
+    if e.request.grep.pcre2 {
+        #[cfg(feature = "pcre2")]
+        {
+            let matcher = build_pcre2_matcher(&e.request.grep)?;
+            return Ok(e.files.iter().map(|selection| apply_file(&e.request, selection, &matcher, forced_encoding)).collect());
+        }
+        #[cfg(not(feature = "pcre2"))]
+        return Err("PCRE2 is not available in this build".to_string());
+    }
+
+    let matcher = build_matcher(&e.request.grep)?;
+    Ok(e.files.iter().map(|selection| apply_file(&e.request, selection, &matcher, forced_encoding)).collect())
+}
+
+fn preview_replace<M: Matcher>(e: &ReplaceRequest, files: Vec<String>, matcher: M, forced_encoding: Option<&'static encoding_rs::Encoding>) -> Vec<ReplacePreview> {
+    let mut previews = Vec::new();
+    for full_path in files {
+        if exceeds_max_filesize(&e.grep, &full_path) {
+            continue;
+        }
+        let Ok(bytes) = std::fs::read(&full_path) else {
+            continue;
+        };
+        let hash = format!("{:016x}", helper::hash_bytes(&bytes));
+        let Some(decoded) = decode_for_replace(bytes, forced_encoding) else {
+            continue;
+        };
+
+        let changes: Vec<ReplaceChange> = decoded
+            .content
+            .split_inclusive('\n')
+            .zip(1..)
+            .filter_map(|(line, line_number)| {
+                replace_line(&matcher, &e.grep, &e.replacement, line).map(|after| ReplaceChange {
+                    line_number,
+                    before: line.to_string(),
+                    after,
+                })
+            })
+            .collect();
+
+        if !changes.is_empty() {
+            previews.push(ReplacePreview {
+                full_path,
+                encoding: decoded.encoding,
+                hash,
+                changes,
+            });
+        }
+    }
+
+    previews
+}
+
+fn apply_file<M: Matcher>(e: &ReplaceRequest, selection: &ReplaceSelection, matcher: &M, forced_encoding: Option<&'static encoding_rs::Encoding>) -> ReplaceOutcome {
+    let outcome = |status: ReplaceStatus, replaced: usize, message: Option<String>| ReplaceOutcome {
+        full_path: selection.full_path.clone(),
+        status,
+        replaced,
+        message,
+    };
+
+    if exceeds_max_filesize(&e.grep, &selection.full_path) {
+        return outcome(ReplaceStatus::Failed, 0, Some("File is larger than the maximum file size".to_string()));
+    }
+
+    let bytes = match std::fs::read(&selection.full_path) {
+        Ok(bytes) => bytes,
+        Err(e) => return outcome(ReplaceStatus::Failed, 0, Some(e.to_string())),
+    };
+
+    if format!("{:016x}", helper::hash_bytes(&bytes)) != selection.hash {
+        return outcome(ReplaceStatus::Conflict, 0, Some("File has changed since the preview".to_string()));
+    }
+
+    let Some(decoded) = decode_for_replace(bytes, forced_encoding) else {
+        return outcome(ReplaceStatus::Failed, 0, Some("File cannot be decoded without loss".to_string()));
+    };
+
+    let mut replaced = 0;
+    let mut data = String::with_capacity(decoded.content.len());
+    for (line, line_number) in decoded.content.split_inclusive('\n').zip(1..) {
+        match replace_line(matcher, &e.grep, &e.replacement, line) {
+            Some(after) if selection.line_numbers.contains(&line_number) => {
+                replaced += 1;
+                data.push_str(&after);
+            }
+            _ => data.push_str(line),
+        }
+    }
+
+    if replaced == 0 {
+        return outcome(ReplaceStatus::Replaced, 0, None);
+    }
+
+    let info = crate::WriteFileInfo {
+        fullPath: selection.full_path.clone(),
+        data,
+        encoding: Some(decoded.encoding),
+        bom: decoded.bom,
+        eol: Some(decoded.eol),
+    };
+
+    match helper::write_to_file(info) {
+        Ok(_) => outcome(ReplaceStatus::Replaced, replaced, None),
+        Err(e) => outcome(ReplaceStatus::Failed, 0, Some(e.message)),
+    }
+}
+
+/// Decodes a file for replacing. Binary files and files with undecodable bytes are left alone,
+/// because writing them back would not round-trip.
+fn decode_for_replace(bytes: Vec<u8>, forced_encoding: Option<&'static encoding_rs::Encoding>) -> Option<helper::ReadResult> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let decoded = helper::decode_as(bytes, forced_encoding.map(|encoding| encoding.name()));
+    if decoded.binary || !decoded.invalid_ranges.is_empty() {
+        None
+    } else {
+        Some(decoded)
+    }
+}
+
+fn exceeds_max_filesize(e: &GrepRequest, full_path: &str) -> bool {
+    match e.max_filesize {
+        Some(max_filesize) => std::fs::metadata(full_path).map(|metadata| metadata.len() > max_filesize).unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Returns the replaced line, or None when nothing matched.
+/// The line terminator is kept out of the haystack so `$` matches and replacements cannot remove it.
+fn replace_line<M: Matcher>(matcher: &M, e: &GrepRequest, replacement: &str, line: &str) -> Option<String> {
+    let text = line.trim_end_matches(['\r', '\n']);
+    let terminator = &line[text.len()..];
+    let haystack = text.as_bytes();
+    let mut dst = Vec::with_capacity(haystack.len());
+    let mut count = 0;
+
+    let replaced = if e.regexp {
+        let mut captures = matcher.new_captures().ok()?;
+        matcher.replace_with_captures(haystack, &mut captures, &mut dst, |captures, dst| {
+            count += 1;
+            captures.interpolate(|name| matcher.capture_index(name), haystack, replacement.as_bytes(), dst);
+            true
+        })
+    } else {
+        matcher.replace(haystack, &mut dst, |_, dst| {
+            count += 1;
+            dst.extend_from_slice(replacement.as_bytes());
+            true
+        })
+    };
+
+    if replaced.is_err() || count == 0 {
+        return None;
+    }
+
+    dst.extend_from_slice(terminator.as_bytes());
+    String::from_utf8(dst).ok()
+}
+
+fn emit<S: Serialize + Clone>(window: &tauri::WebviewWindow, event: &str, payload: S) {
+    if let Err(e) = window.emit_to(
+        EventTarget::WebviewWindow {
+            label: window.label().to_string(),
+        },
+        event,
+        payload,
+    ) {
+        eprintln!("[FS_ERR] Failed to emit {}: {:?}", event, e);
+    }
+}
+
+/// Cancels the job only when the window started it, so a window cannot stop another window's search
+pub fn cancel(label: &str, job_id: u32) {
+    if let Some(job) = JOBS.lock().unwrap().get(&job_id).filter(|job| job.label == label) {
+        job.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn cancel_window(label: &str) {
+    for job in JOBS.lock().unwrap().values().filter(|job| job.label == label) {
+        job.cancelled.store(true, Ordering::Relaxed);
     }
 }
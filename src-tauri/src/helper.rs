@@ -1,55 +1,124 @@
 use crate::{
-    fgrep::{self, GrepRequest},
+    cli::{CliArgs, STDIN},
+    fgrep::GrepRequest,
     session::Session,
     watcher::{self, WatchTx},
     WriteFileInfo,
 };
-use encoding_rs::Encoding;
+use encoding_rs::{DecoderResult, Encoding};
 use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        LazyLock, Mutex,
+    },
+};
 use tauri::{AppHandle, Manager};
 
-pub fn setup(app: &mut tauri::App, args: Vec<String>) {
+/// Exit code for a caller waiting with --wait. Set when the user closes without saving.
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+static SELF_WRITES: LazyLock<Mutex<HashMap<PathBuf, SelfWrite>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Files last read with bytes the decoded content cannot keep. Saving over them would replace those bytes.
+static LOSSY_FILES: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+type LineEnding = (u64, &'static str);
+
+/// Line endings of files with mixed line endings.
+/// The editor normalizes line endings, so saving puts the original terminator back on every line it can match.
+static MIXED_EOLS: LazyLock<Mutex<HashMap<PathBuf, Vec<LineEnding>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct SelfWrite {
+    hash: u64,
+    len: u64,
+}
+
+pub fn setup(app: &mut tauri::App, args: CliArgs) {
     let id = &app.config().identifier;
-    if let Ok(session) = crate::session::start(id) {
+    if let Ok(session) = crate::session::start(id, app.app_handle()) {
         app.manage(session);
     }
+    if args.forwarded {
+        app.manage(Forwarded);
+    }
 
     let (tx_cmd, rx_cmd) = crossbeam_channel::bounded(1);
     app.manage(WatchTx(tx_cmd));
     watcher::spwan_watcher(app.app_handle(), rx_cmd).unwrap();
 
-    if args.len() == 1 {
-        app.manage(FileArg::default());
+    if let Some(grep) = args.grep {
+        app.manage(grep);
         return;
     }
 
-    if args[1] == "-g" {
-        let req = fgrep::GrepRequest {
-            condition: args[2].to_string(),
-            start_directory: args[3].to_string(),
-            file_type: args[4].to_string(),
-            match_by_word: args.contains(&"-m".to_string()),
-            case_sensitive: args.contains(&"-c".to_string()),
-            regexp: args.contains(&"-r".to_string()),
-            recursive: args.contains(&"-s".to_string()),
-        };
-        app.manage(req);
-    } else {
-        let file = FileArg {
-            file_path: Some(args[1].to_string()),
-            content: None,
-            encoding: None,
-            start_line: if args.len() > 2 {
-                Some(Selection {
-                    column: args[2].parse().unwrap(),
-                    row: args[3].parse().unwrap(),
-                })
-            } else {
-                None
-            },
-        };
-        app.manage(file);
+    let mut files: Vec<FileArg> = args
+        .files
+        .into_iter()
+        .map(|file| FileArg {
+            file_path: Some(file.path),
+            encoding: args.encoding.clone(),
+            readonly: args.readonly,
+            start_line: file.line.map(|row| Selection {
+                column: file.column.unwrap_or(1),
+                row,
+            }),
+            ..Default::default()
+        })
+        .collect();
+
+    /* The view opens every file but the first in new windows, so stdin has to come first */
+    if let Some(index) = files.iter().position(|file| file.file_path.as_deref() == Some(STDIN)) {
+        let mut file = files.remove(index);
+        file.file_path = None;
+        file.pipe = args.wait;
+        read_stdin(&mut file);
+        files.insert(0, file);
     }
+
+    app.manage(files);
+}
+
+fn read_stdin(file: &mut FileArg) {
+    let mut bytes = Vec::new();
+    if let Err(e) = std::io::stdin().read_to_end(&mut bytes) {
+        eprintln!("[FS_ERR] Failed to read stdin: {}", e);
+    }
+
+    let result = decode_as(bytes, file.encoding.as_deref());
+    file.content = Some(result.content);
+    file.encoding = Some(result.encoding);
+    file.bom = result.bom;
+    file.eol = Some(result.eol);
+    file.binary = result.binary;
+    file.invalid_ranges = result.invalid_ranges;
+}
+
+/// The instance is told not to forward args back to the running one.
+pub fn open_window(app: &AppHandle, args: &[String], current_dir: Option<&str>) -> Result<u32, String> {
+    let app_path = tauri::process::current_binary(&app.env()).map_err(|e| e.to_string())?;
+    let mut command = std::process::Command::new(app_path);
+    command.arg("--new-window").args(args);
+    if let Some(current_dir) = current_dir {
+        command.current_dir(current_dir);
+    }
+    let mut child = command.spawn().map_err(|e| e.to_string())?;
+    let pid = child.id();
+    std::thread::spawn(move || child.wait());
+    Ok(pid)
+}
+
+pub fn set_exit_code(code: i32) {
+    EXIT_CODE.store(code, Ordering::Relaxed);
+}
+
+pub fn exit_code() -> i32 {
+    EXIT_CODE.load(Ordering::Relaxed)
 }
 
 pub fn exit(app: &tauri::AppHandle) {
@@ -69,48 +138,44 @@ pub struct FileArg {
     pub file_path: Option<String>,
     pub content: Option<String>,
     pub encoding: Option<String>,
+    pub bom: bool,
+    pub eol: Option<String>,
+    pub binary: bool,
+    pub invalid_ranges: Vec<(usize, usize)>,
     pub start_line: Option<Selection>,
+    pub readonly: bool,
+    pub pipe: bool,
 }
+pub struct Forwarded;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct InitArgs {
-    file: Option<FileArg>,
+    /// Files from the command line. Only the first one is read, the view opens the rest in new windows.
+    files: Vec<FileArg>,
     grep: Option<GrepRequest>,
     locales: Vec<String>,
     app_data_dir: String,
     restore_position: bool,
+    pcre2: bool,
 }
 
 pub fn get_init_args(app: AppHandle) -> Result<InitArgs, String> {
     let locale = zouni::shell::get_locale();
-    let restore_position = app.try_state::<Session>().is_some();
+    /* A forwarded launch would have owned the session if no instance were running */
+    let restore_position = app.try_state::<Session>().is_some() || app.try_state::<Forwarded>().is_some();
     let mut args = InitArgs {
         locales: vec![locale],
         restore_position,
+        pcre2: cfg!(feature = "pcre2"),
         app_data_dir: app.path().app_data_dir().unwrap_or_default().to_string_lossy().to_string(),
         ..Default::default()
     };
 
-    if let Some(file_args) = app.try_state::<FileArg>() {
-        let mut file = file_args.inner().clone();
-        let (content, encoding) = if let Some(file_path) = &file_args.file_path {
-            let bytes = std::fs::read(file_path).map_err(|e| e.to_string())?;
-            if bytes.is_empty() {
-                (None, None)
-            } else {
-                let mut detector = chardetng::EncodingDetector::new();
-                if detector.feed(&bytes, true) {
-                    let result = detector.guess(None, true).decode(&bytes);
-                    (Some(result.0.to_string()), Some(result.1.name().to_string()))
-                } else {
-                    (Some(unsafe { String::from_utf8_unchecked(bytes) }), None)
-                }
-            }
-        } else {
-            (None, None)
-        };
-        file.content = content;
-        file.encoding = encoding;
-        args.file = Some(file);
+    if let Some(files) = app.try_state::<Vec<FileArg>>() {
+        args.files = files.inner().clone();
+        if let Some(file) = args.files.first_mut() {
+            load_file(file)?;
+        }
         return Ok(args);
     }
 
@@ -122,29 +187,298 @@ pub fn get_init_args(app: AppHandle) -> Result<InitArgs, String> {
     Ok(args)
 }
 
+fn load_file(file: &mut FileArg) -> Result<(), String> {
+    let Some(file_path) = &file.file_path else {
+        return Ok(());
+    };
+
+    let bytes = std::fs::read(file_path).map_err(|e| e.to_string())?;
+    if !bytes.is_empty() {
+        let result = decode_as(bytes, file.encoding.as_deref());
+        track_read(Path::new(file_path), &result);
+        file.readonly |= result.is_lossy();
+        file.content = Some(result.content);
+        file.encoding = Some(result.encoding);
+        file.bom = result.bom;
+        file.eol = Some(result.eol);
+        file.binary = result.binary;
+        file.invalid_ranges = result.invalid_ranges;
+    }
+
+    Ok(())
+}
+
+const EOL_LF: &str = "lf";
+const EOL_CRLF: &str = "crlf";
+const EOL_CR: &str = "cr";
+const EOL_MIXED: &str = "mixed";
+const BINARY_CHECK_LENGTH: usize = 8000;
+const DETECT_LENGTH: usize = 64 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ReadResult {
     pub content: String,
     pub encoding: String,
+    pub bom: bool,
+    pub eol: String,
+    pub binary: bool,
+    pub invalid_ranges: Vec<(usize, usize)>,
 }
+
+impl ReadResult {
+    pub fn is_lossy(&self) -> bool {
+        self.binary || !self.invalid_ranges.is_empty()
+    }
+}
+
 pub fn read_to_string(file_path: &str) -> Result<ReadResult, String> {
     let bytes = std::fs::read(file_path).map_err(|e| e.to_string())?;
-    let (content, encoding) = if bytes.is_empty() {
-        (String::new(), encoding_rs::UTF_8.name().to_string())
-    } else {
-        let mut detector = chardetng::EncodingDetector::new();
-        if detector.feed(&bytes, true) {
-            let result = detector.guess(None, true).decode(&bytes);
-            (result.0.to_string(), result.1.name().to_string())
-        } else {
-            (unsafe { String::from_utf8_unchecked(bytes) }, encoding_rs::UTF_8.name().to_string())
+    let result = if bytes.is_empty() {
+        ReadResult {
+            content: String::new(),
+            encoding: encoding_rs::UTF_8.name().to_string(),
+            bom: false,
+            eol: default_eol().to_string(),
+            binary: false,
+            invalid_ranges: Vec::new(),
         }
+    } else {
+        decode(bytes)
+    };
+
+    track_read(Path::new(file_path), &result);
+    Ok(result)
+}
+
+fn track_read(file_path: &Path, result: &ReadResult) {
+    let mut lossy_files = LOSSY_FILES.lock().unwrap();
+    if result.is_lossy() {
+        lossy_files.insert(file_path.to_path_buf());
+    } else {
+        lossy_files.remove(file_path);
+    }
+    drop(lossy_files);
+
+    track_line_endings(file_path, &result.content);
+}
+
+fn track_line_endings(file_path: &Path, content: &str) {
+    let mut mixed_eols = MIXED_EOLS.lock().unwrap();
+    if detect_eol(content) == EOL_MIXED {
+        let lines = split_lines(content).into_iter().map(|(text, terminator)| (hash_bytes(text.as_bytes()), terminator)).collect();
+        mixed_eols.insert(file_path.to_path_buf(), lines);
+    } else {
+        mixed_eols.remove(file_path);
+    }
+}
+
+/// Splits content into lines and their terminators. The last line has an empty terminator.
+fn split_lines(content: &str) -> Vec<(&str, &'static str)> {
+    let bytes = content.as_bytes();
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let terminator = match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => "\r\n",
+            b'\r' => "\r",
+            b'\n' => "\n",
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        lines.push((&content[start..i], terminator));
+        i += terminator.len();
+        start = i;
+    }
+    lines.push((&content[start..], ""));
+    lines
+}
+
+pub fn decode(bytes: Vec<u8>) -> ReadResult {
+    decode_as(bytes, None)
+}
+
+pub fn decode_as(bytes: Vec<u8>, label: Option<&str>) -> ReadResult {
+    let (encoding, bom_length) = match label.and_then(|label| Encoding::for_label_no_replacement(label.as_bytes())) {
+        Some(encoding) => (encoding, Encoding::for_bom(&bytes).filter(|(bom, _)| *bom == encoding).map_or(0, |(_, bom_length)| bom_length)),
+        None => detect_encoding(&bytes, true),
     };
 
-    Ok(ReadResult {
+    let (content, invalid_ranges) = decode_lossless(encoding, &bytes[bom_length..], bom_length);
+    let eol = detect_eol(&content).to_string();
+    let binary = is_binary(encoding, &bytes[bom_length..]);
+
+    ReadResult {
         content,
-        encoding,
-    })
+        encoding: encoding.name().to_string(),
+        bom: bom_length > 0,
+        eol,
+        binary,
+        invalid_ranges,
+    }
+}
+
+/// Returns the encoding from the BOM if any, otherwise guessed by chardetng, along with the BOM length.
+/// Set last to false when bytes is only the head of the file.
+pub fn detect_encoding(bytes: &[u8], last: bool) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return (encoding, bom_length);
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    if detector.feed(bytes, last) {
+        (detector.guess(None, true), 0)
+    } else {
+        (encoding_rs::UTF_8, 0)
+    }
+}
+
+pub fn detect_file_encoding(file: &mut std::fs::File, size: u64) -> Result<(&'static Encoding, usize), String> {
+    let mut head = vec![0; DETECT_LENGTH.min(size as usize)];
+    file.read_exact(&mut head).map_err(|e| e.to_string())?;
+    Ok(detect_encoding(&head, head.len() as u64 == size))
+}
+
+/// Decodes bytes replacing malformed sequences with U+FFFD and returns the byte ranges that were replaced.
+/// Ranges are shifted by offset so they point into the original file.
+fn decode_lossless(encoding: &'static Encoding, bytes: &[u8], offset: usize) -> (String, Vec<(usize, usize)>) {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut content = String::with_capacity(decoder.max_utf8_buffer_length_without_replacement(bytes.len()).unwrap_or(bytes.len()));
+    let mut invalid_ranges: Vec<(usize, usize)> = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let (result, read) = decoder.decode_to_string_without_replacement(&bytes[position..], &mut content, true);
+        position += read;
+        match result {
+            DecoderResult::InputEmpty => break,
+            DecoderResult::OutputFull => {
+                let remaining = bytes.len() - position;
+                content.reserve(decoder.max_utf8_buffer_length_without_replacement(remaining).unwrap_or(remaining).max(4));
+            }
+            DecoderResult::Malformed(malformed, consumed_after) => {
+                let end = offset + position - consumed_after as usize;
+                let start = end - malformed as usize;
+                match invalid_ranges.last_mut() {
+                    Some(last) if last.1 == start => last.1 = end,
+                    _ => invalid_ranges.push((start, end)),
+                }
+                content.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+    }
+
+    (content, invalid_ranges)
+}
+
+/// Treats the file as binary when a NUL byte appears near the start, the same heuristic git and ripgrep use.
+/// UTF-16 text is full of NUL bytes, so it is never classified as binary.
+fn is_binary(encoding: &'static Encoding, bytes: &[u8]) -> bool {
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        return false;
+    }
+
+    bytes[..bytes.len().min(BINARY_CHECK_LENGTH)].contains(&0)
+}
+
+fn default_eol() -> &'static str {
+    if cfg!(windows) {
+        EOL_CRLF
+    } else {
+        EOL_LF
+    }
+}
+
+fn detect_eol(content: &str) -> &'static str {
+    let bytes = content.as_bytes();
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                crlf += 1;
+                i += 1;
+            }
+            b'\r' => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => default_eol(),
+        (true, false, false) => EOL_LF,
+        (false, true, false) => EOL_CRLF,
+        (false, false, true) => EOL_CR,
+        _ => EOL_MIXED,
+    }
+}
+
+fn apply_eol<'a>(file_path: &Path, data: &'a str, eol: Option<&str>) -> Cow<'a, str> {
+    if eol == Some(EOL_MIXED) {
+        return restore_line_endings(file_path, data);
+    }
+
+    let line_break = match eol {
+        Some(EOL_LF) => "\n",
+        Some(EOL_CRLF) => "\r\n",
+        Some(EOL_CR) => "\r",
+        _ => return Cow::Borrowed(data),
+    };
+
+    if detect_eol(data) == eol.unwrap_or_default() || !data.contains(['\r', '\n']) {
+        return Cow::Borrowed(data);
+    }
+
+    let normalized = data.replace("\r\n", "\n").replace('\r', "\n");
+    if line_break == "\n" {
+        Cow::Owned(normalized)
+    } else {
+        Cow::Owned(normalized.replace('\n', line_break))
+    }
+}
+
+/// Gives lines unchanged from the top or the bottom of the file their original terminator, and the rest the most common one
+fn restore_line_endings<'a>(file_path: &Path, data: &'a str) -> Cow<'a, str> {
+    let mixed_eols = MIXED_EOLS.lock().unwrap();
+    let Some(original) = mixed_eols.get(file_path) else {
+        return Cow::Borrowed(data);
+    };
+
+    let lines = split_lines(data);
+    let hashes: Vec<u64> = lines.iter().map(|(text, _)| hash_bytes(text.as_bytes())).collect();
+    let prefix = hashes.iter().zip(original).take_while(|(hash, (original, _))| *hash == original).count();
+    let suffix = hashes[prefix..].iter().rev().zip(original[prefix..].iter().rev()).take_while(|(hash, (original, _))| *hash == original).count();
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, terminator) in original.iter().filter(|(_, terminator)| !terminator.is_empty()) {
+        *counts.entry(terminator).or_default() += 1;
+    }
+    let common = counts.into_iter().max_by_key(|(_, count)| *count).map_or("\n", |(terminator, _)| terminator);
+
+    let mut restored = String::with_capacity(data.len() + lines.len());
+    for (i, (text, terminator)) in lines.iter().enumerate() {
+        restored.push_str(text);
+        if terminator.is_empty() {
+            continue;
+        }
+        let kept = if i < prefix {
+            original[i].1
+        } else if i >= lines.len() - suffix {
+            original[original.len() - (lines.len() - i)].1
+        } else {
+            ""
+        };
+        restored.push_str(if kept.is_empty() {
+            common
+        } else {
+            kept
+        });
+    }
+    Cow::Owned(restored)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -165,21 +499,289 @@ pub fn encode(arg: EncodeArg) -> Result<String, String> {
     }
 }
 
-pub fn write_to_file(info: WriteFileInfo) -> Result<(), String> {
-    if let Some(encoding_label) = &info.encoding {
-        let encoding = Encoding::for_label(encoding_label.as_bytes()).unwrap_or(encoding_rs::UTF_8);
-        if encoding == encoding_rs::UTF_8 {
-            return write_raw(info);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteErrorKind {
+    Resolve,
+    CreateTemp,
+    Write,
+    Sync,
+    Permissions,
+    Rename,
+    Lossy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteError {
+    pub kind: WriteErrorKind,
+    pub file_path: String,
+    pub message: String,
+}
+
+impl WriteError {
+    fn new(kind: WriteErrorKind, file_path: &Path, error: std::io::Error) -> Self {
+        Self {
+            kind,
+            file_path: file_path.to_string_lossy().to_string(),
+            message: error.to_string(),
         }
+    }
+}
+
+pub fn write_to_file(info: WriteFileInfo) -> Result<(), WriteError> {
+    let file_path = Path::new(&info.fullPath);
+    if LOSSY_FILES.lock().unwrap().contains(file_path) {
+        return Err(WriteError {
+            kind: WriteErrorKind::Lossy,
+            file_path: info.fullPath.clone(),
+            message: "The file is binary or has bytes that cannot be decoded, so saving it would corrupt it".to_string(),
+        });
+    }
+
+    let data = apply_eol(file_path, &info.data, info.eol.as_deref());
+    let bytes = to_bytes(&info, &data);
+    write_atomic(file_path, &bytes)?;
+    record_self_write(file_path, &bytes);
+    track_line_endings(file_path, &data);
+    Ok(())
+}
+
+pub fn write_to_stdout(info: WriteFileInfo) -> Result<(), String> {
+    let mut stdout = std::io::stdout().lock();
+    let data = apply_eol(Path::new(&info.fullPath), &info.data, info.eol.as_deref());
+    stdout.write_all(&to_bytes(&info, &data)).and_then(|_| stdout.flush()).map_err(|e| e.to_string())
+}
+
+fn to_bytes(info: &WriteFileInfo, data: &str) -> Vec<u8> {
+    let encoding = info.encoding.as_ref().and_then(|label| Encoding::for_label(label.as_bytes())).unwrap_or(encoding_rs::UTF_8);
+
+    let mut bytes = Vec::with_capacity(data.len() + 3);
+    if info.bom {
+        bytes.extend_from_slice(get_bom(encoding));
+    }
+    bytes.extend_from_slice(&encode_text(encoding, data));
+    bytes
+}
+
+fn record_self_write(file_path: &Path, bytes: &[u8]) {
+    if let Ok(metadata) = std::fs::metadata(file_path) {
+        let self_write = SelfWrite {
+            hash: hash_bytes(bytes),
+            len: metadata.len(),
+        };
+        SELF_WRITES.lock().unwrap().insert(file_path.to_path_buf(), self_write);
+    }
+}
+
+pub fn is_self_write(file_path: &Path) -> bool {
+    let self_writes = SELF_WRITES.lock().unwrap();
+    let Some(self_write) = self_writes.get(file_path) else {
+        return false;
+    };
+
+    let Ok(metadata) = std::fs::metadata(file_path) else {
+        return false;
+    };
+
+    // The length can only rule a self write out. Content with the same length or mtime may still differ.
+    if metadata.len() != self_write.len {
+        return false;
+    }
+
+    std::fs::read(file_path).map(|bytes| hash_bytes(&bytes) == self_write.hash).unwrap_or(false)
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
 
-        // Encode if not UTF-8
-        let encoded = encoding.encode(&info.data);
-        std::fs::write(info.fullPath, encoded.0).map_err(|e| e.to_string())
+fn get_bom(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == encoding_rs::UTF_8 {
+        b"\xEF\xBB\xBF"
+    } else if encoding == encoding_rs::UTF_16LE {
+        b"\xFF\xFE"
+    } else if encoding == encoding_rs::UTF_16BE {
+        b"\xFE\xFF"
     } else {
-        write_raw(info)
+        b""
     }
 }
 
-fn write_raw(info: WriteFileInfo) -> Result<(), String> {
-    std::fs::write(info.fullPath, info.data.as_bytes()).map_err(|e| e.to_string())
+fn encode_text<'a>(encoding: &'static Encoding, data: &'a str) -> Cow<'a, [u8]> {
+    // encoding_rs never encodes to UTF-16, so do it by hand
+    if encoding == encoding_rs::UTF_16LE {
+        Cow::Owned(data.encode_utf16().flat_map(u16::to_le_bytes).collect())
+    } else if encoding == encoding_rs::UTF_16BE {
+        Cow::Owned(data.encode_utf16().flat_map(u16::to_be_bytes).collect())
+    } else {
+        encoding.encode(data).0
+    }
+}
+
+/// Writes to a temporary file next to the target and renames it over the target,
+/// so the original is never left half-written.
+fn write_atomic(file_path: &Path, bytes: &[u8]) -> Result<(), WriteError> {
+    // Write through symlinks instead of replacing the link itself
+    let target = match std::fs::symlink_metadata(file_path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => std::fs::canonicalize(file_path).map_err(|e| WriteError::new(WriteErrorKind::Resolve, file_path, e))?,
+        _ => file_path.to_path_buf(),
+    };
+    let original = std::fs::metadata(&target).ok();
+
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    let (temp_path, mut temp_file) = create_temp_file(&dir, &file_name).map_err(|e| WriteError::new(WriteErrorKind::CreateTemp, &target, e))?;
+
+    let result = fill_temp_file(&mut temp_file, &temp_path, bytes, original.as_ref(), &target);
+    drop(temp_file);
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    if let Err(e) = std::fs::rename(&temp_path, &target) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(WriteError::new(WriteErrorKind::Rename, &target, e));
+    }
+
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Ok(dir) = std::fs::File::open(&dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+fn create_temp_file(dir: &Path, file_name: &str) -> std::io::Result<(PathBuf, std::fs::File)> {
+    let pid = std::process::id();
+    let mut attempt = 0;
+    loop {
+        let temp_path = dir.join(format!(".{}.{}.{}.tmp", file_name, pid, attempt));
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn fill_temp_file(temp_file: &mut std::fs::File, temp_path: &Path, bytes: &[u8], original: Option<&std::fs::Metadata>, target: &Path) -> Result<(), WriteError> {
+    temp_file.write_all(bytes).map_err(|e| WriteError::new(WriteErrorKind::Write, target, e))?;
+
+    if let Some(metadata) = original {
+        std::fs::set_permissions(temp_path, metadata.permissions()).map_err(|e| WriteError::new(WriteErrorKind::Permissions, target, e))?;
+
+        // Keeping the owner needs privileges we may not have, so this is best effort
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let _ = std::os::unix::fs::fchown(&*temp_file, Some(metadata.uid()), Some(metadata.gid()));
+        }
+    }
+
+    temp_file.sync_all().map_err(|e| WriteError::new(WriteErrorKind::Sync, target, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("editor-helper-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join(name);
+        std::fs::write(&file_path, bytes).unwrap();
+        file_path
+    }
+
+    fn save(file_path: &Path, data: &str) -> Result<(), WriteError> {
+        write_to_file(WriteFileInfo {
+            fullPath: file_path.to_string_lossy().to_string(),
+            data: data.to_string(),
+            encoding: Some("utf-8".to_string()),
+            bom: false,
+            eol: None,
+        })
+    }
+
+    #[test]
+    fn lossy_file_opens_read_only_and_cannot_be_saved() {
+        for (name, bytes) in [("binary.txt", b"abc\0def\n".as_slice()), ("invalid.txt", b"caf\xe9\n".as_slice())] {
+            let file_path = temp_file(name, bytes);
+            let mut file = FileArg {
+                file_path: Some(file_path.to_string_lossy().to_string()),
+                encoding: Some("utf-8".to_string()),
+                ..Default::default()
+            };
+            load_file(&mut file).unwrap();
+            assert!(file.readonly, "{}", name);
+
+            let error = save(&file_path, file.content.as_deref().unwrap()).unwrap_err();
+            assert!(matches!(error.kind, WriteErrorKind::Lossy));
+            assert_eq!(std::fs::read(&file_path).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn file_can_be_saved_once_read_without_loss() {
+        let file_path = temp_file("fixed.txt", b"abc\0def\n");
+        assert!(read_to_string(file_path.to_str().unwrap()).unwrap().is_lossy());
+        assert!(save(&file_path, "text\n").is_err());
+
+        std::fs::write(&file_path, b"abcdef\n").unwrap();
+        assert!(!read_to_string(file_path.to_str().unwrap()).unwrap().is_lossy());
+        save(&file_path, "text\n").unwrap();
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"text\n");
+    }
+
+    #[test]
+    fn same_length_and_mtime_is_not_a_self_write() {
+        let file_path = temp_file("self.txt", b"");
+        save(&file_path, "abc\n").unwrap();
+        assert!(is_self_write(&file_path));
+
+        let modified = std::fs::metadata(&file_path).unwrap().modified().unwrap();
+        std::fs::write(&file_path, b"xyz\n").unwrap();
+        std::fs::File::options().write(true).open(&file_path).unwrap().set_modified(modified).unwrap();
+        assert!(!is_self_write(&file_path));
+    }
+
+    #[test]
+    fn mixed_line_endings_survive_a_save() {
+        let bytes = b"one\r\ntwo\nthree\rfour\r\nfive";
+        let file_path = temp_file("mixed.txt", bytes);
+        let mut file = FileArg {
+            file_path: Some(file_path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        load_file(&mut file).unwrap();
+        assert_eq!(file.eol.as_deref(), Some(EOL_MIXED));
+
+        // The editor hands back the content with its own line endings
+        let normalized = file.content.unwrap().replace("\r\n", "\n").replace('\r', "\n");
+        let save = |data: &str| {
+            write_to_file(WriteFileInfo {
+                fullPath: file_path.to_string_lossy().to_string(),
+                data: data.to_string(),
+                encoding: file.encoding.clone(),
+                bom: file.bom,
+                eol: file.eol.clone(),
+            })
+            .unwrap()
+        };
+
+        save(&normalized);
+        assert_eq!(std::fs::read(&file_path).unwrap(), bytes);
+
+        save(&normalized.replace("three\n", "three\nnew\n"));
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"one\r\ntwo\nthree\rnew\r\nfour\r\nfive");
+    }
 }
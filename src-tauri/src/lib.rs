@@ -4,11 +4,14 @@ use serde::{Deserialize, Serialize};
 use std::{env, path::PathBuf};
 use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
 use zouni::*;
+mod cli;
 mod dialog;
 mod fgrep;
 mod helper;
+mod line_index;
 mod menu;
 mod session;
+mod stream;
 mod watcher;
 
 #[cfg(target_os = "linux")]
@@ -77,19 +80,67 @@ fn read_text_file(payload: String) -> Result<helper::ReadResult, String> {
     helper::read_to_string(&payload).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn open_stream(payload: String) -> Result<stream::StreamInfo, String> {
+    stream::open(&payload)
+}
+
+#[tauri::command]
+fn read_chunk(payload: stream::ChunkRequest) -> Result<stream::Chunk, String> {
+    stream::read_chunk(payload)
+}
+
+#[tauri::command]
+fn close_stream(payload: u32) {
+    stream::close(payload);
+}
+
+#[tauri::command]
+fn build_line_index(window: WebviewWindow, payload: String) {
+    line_index::build(&window, payload);
+}
+
+#[tauri::command]
+async fn line_to_offset(payload: line_index::LineQuery) -> Result<u64, String> {
+    line_index::line_to_offset(payload)
+}
+
+#[tauri::command]
+async fn offset_to_line(payload: line_index::OffsetQuery) -> Result<u64, String> {
+    line_index::offset_to_line(payload)
+}
+
+#[tauri::command]
+async fn read_lines(payload: line_index::LineRangeRequest) -> Result<line_index::LineRange, String> {
+    line_index::read_lines(payload)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 struct WriteFileInfo {
     fullPath: String,
     data: String,
     encoding: Option<String>,
+    #[serde(default)]
+    bom: bool,
+    eol: Option<String>,
 }
 
 #[tauri::command]
-fn write_text_file(payload: WriteFileInfo) -> Result<(), String> {
+fn write_text_file(payload: WriteFileInfo) -> Result<(), helper::WriteError> {
     helper::write_to_file(payload)
 }
 
+#[tauri::command]
+fn set_exit_code(payload: i32) {
+    helper::set_exit_code(payload);
+}
+
+#[tauri::command]
+fn write_stdout(payload: WriteFileInfo) -> Result<(), String> {
+    helper::write_to_stdout(payload)
+}
+
 #[tauri::command]
 fn prepare_menu(window: WebviewWindow) {
     let window_handle = get_window_handel(&window);
@@ -126,18 +177,70 @@ async fn open_list_context_menu(window: WebviewWindow, payload: menu::Position)
 }
 
 #[tauri::command]
-fn watch(app: AppHandle, payload: String) -> Result<(), String> {
-    if let Some(tx) = app.try_state::<WatchTx>() {
-        tx.inner().0.send(WatcherCommand::Watch(payload)).map_err(|e| e.to_string())
+fn watch(window: WebviewWindow, payload: String) -> Result<(), String> {
+    if let Some(tx) = window.app_handle().try_state::<WatchTx>() {
+        tx.inner()
+            .0
+            .send(WatcherCommand::Watch {
+                path: payload,
+                label: window.label().to_string(),
+            })
+            .map_err(|e| e.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn follow(window: WebviewWindow, payload: String) -> Result<(), String> {
+    if let Some(tx) = window.app_handle().try_state::<WatchTx>() {
+        tx.inner()
+            .0
+            .send(WatcherCommand::Follow {
+                path: payload,
+                label: window.label().to_string(),
+            })
+            .map_err(|e| e.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn unwatch(window: WebviewWindow, payload: String) -> Result<(), String> {
+    if let Some(tx) = window.app_handle().try_state::<WatchTx>() {
+        tx.inner()
+            .0
+            .send(WatcherCommand::Unwatch {
+                path: payload,
+                label: window.label().to_string(),
+            })
+            .map_err(|e| e.to_string())
     } else {
         Ok(())
     }
 }
 
 #[tauri::command]
-fn unwatch(app: AppHandle, payload: String) -> Result<(), String> {
-    if let Some(tx) = app.try_state::<WatchTx>() {
-        tx.inner().0.send(WatcherCommand::Unwatch(payload)).map_err(|e| e.to_string())
+fn watch_directory(window: WebviewWindow, payload: watcher::DirectoryWatchRequest) -> Result<(), String> {
+    if let Some(tx) = window.app_handle().try_state::<WatchTx>() {
+        let command = WatcherCommand::watch_directory(payload, window.label().to_string())?;
+        tx.inner().0.send(command).map_err(|e| e.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn unwatch_directory(window: WebviewWindow, payload: String) -> Result<(), String> {
+    if let Some(tx) = window.app_handle().try_state::<WatchTx>() {
+        tx.inner()
+            .0
+            .send(WatcherCommand::UnwatchDirectory {
+                path: payload,
+                label: window.label().to_string(),
+            })
+            .map_err(|e| e.to_string())
     } else {
         Ok(())
     }
@@ -153,6 +256,10 @@ struct OpenFileResult {
     file_path: String,
     content: String,
     encoding: String,
+    bom: bool,
+    eol: String,
+    binary: bool,
+    invalid_ranges: Vec<(usize, usize)>,
 }
 #[tauri::command]
 async fn show_open_dialog(payload: DialogOptions) -> Option<OpenFileResult> {
@@ -163,6 +270,10 @@ async fn show_open_dialog(payload: DialogOptions) -> Option<OpenFileResult> {
             file_path,
             content: read_result.content,
             encoding: read_result.encoding,
+            bom: read_result.bom,
+            eol: read_result.eol,
+            binary: read_result.binary,
+            invalid_ranges: read_result.invalid_ranges,
         })
     } else {
         None
@@ -175,16 +286,8 @@ async fn show_save_dialog(payload: DialogOptions) -> Option<String> {
 }
 
 #[tauri::command]
-fn new_window(app: AppHandle, payload: Option<String>) -> Result<(), String> {
-    let app_path = tauri::process::current_binary(&app.env()).map_err(|e| e.to_string())?;
-    if cfg!(windows) {
-        zouni::shell::open_path_with(payload.unwrap_or_default(), app_path)
-    } else {
-        let payload = payload.unwrap_or_default();
-        let args: Vec<&str> = payload.split(" ").collect();
-        std::process::Command::new(app_path).args(args).spawn().map_err(|e| e.to_string())?;
-        Ok(())
-    }
+fn new_window(app: AppHandle, payload: Vec<String>) -> Result<(), String> {
+    helper::open_window(&app, &payload, None).map(|_| ())
 }
 
 #[tauri::command]
@@ -231,13 +334,23 @@ fn unlisten_file_drop() {
 }
 
 #[tauri::command]
-async fn run_grep(window: WebviewWindow, payload: fgrep::GrepRequest) -> Result<Vec<fgrep::GrepResult>, String> {
-    fgrep::run_grep(&window, payload).await
+fn run_grep(window: WebviewWindow, payload: fgrep::GrepRequest) -> Result<u32, String> {
+    fgrep::run_grep(&window, payload)
+}
+
+#[tauri::command]
+fn abort_grep(window: WebviewWindow, payload: u32) {
+    fgrep::cancel(window.label(), payload);
+}
+
+#[tauri::command]
+async fn run_replace(payload: fgrep::ReplaceRequest) -> Result<Vec<fgrep::ReplacePreview>, String> {
+    fgrep::run_replace(payload)
 }
 
 #[tauri::command]
-fn abort_grep() {
-    fgrep::cancel();
+async fn apply_replace(payload: fgrep::ApplyReplaceRequest) -> Result<Vec<fgrep::ReplaceOutcome>, String> {
+    fgrep::apply_replace(payload)
 }
 
 #[tauri::command]
@@ -245,16 +358,67 @@ fn change_encoding(payload: helper::EncodeArg) -> Result<String, String> {
     helper::encode(payload)
 }
 
+/// Release builds on Windows have no console of their own, so anything printed is lost unless the parent's console is attached
+fn attach_console() {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let raw_args: Vec<String> = env::args().collect();
+    let mut args = match cli::parse(&raw_args) {
+        Ok(args) => args,
+        Err(e) => {
+            attach_console();
+            eprintln!("error: {}\n\nRun with --help for usage.", e);
+            std::process::exit(2);
+        }
+    };
+
+    if args.help || args.version || args.headless.is_some() {
+        attach_console();
+    }
+
+    if args.help {
+        println!("{}", cli::usage());
+        std::process::exit(0);
+    }
+
+    if args.version {
+        println!("{}", cli::version());
+        std::process::exit(0);
+    }
+
+    if let Some(format) = args.headless.take() {
+        std::process::exit(fgrep::run_headless(args.grep.take().unwrap_or_default(), format));
+    }
+
+    let context = tauri::generate_context!();
+    /* stdin has to be read by the process that owns the window */
+    if !args.new_window && !args.reads_stdin() {
+        if let Some(code) = session::forward(&context.config().identifier, &raw_args[1..], args.wait) {
+            std::process::exit(code);
+        }
+    }
+
+    /* A caller waiting in another process learns the exit code from the Closed signal */
+    let report_closed = args.forwarded && args.wait;
+
     tauri::Builder::default()
-        .setup(|app| {
-            let args: Vec<String> = env::args().collect();
+        .setup(move |app| {
             helper::setup(app, args);
             Ok(())
         })
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::Destroyed = event {
+                if let Some(tx) = window.app_handle().try_state::<WatchTx>() {
+                    let _ = tx.inner().0.send(WatcherCommand::UnwatchWindow(window.label().to_string()));
+                }
+                fgrep::cancel_window(window.label());
                 helper::exit(window.app_handle());
             }
         })
@@ -271,9 +435,21 @@ pub fn run() {
             mkdir_all,
             create,
             read_text_file,
+            open_stream,
+            read_chunk,
+            close_stream,
+            build_line_index,
+            line_to_offset,
+            offset_to_line,
+            read_lines,
             write_text_file,
+            write_stdout,
+            set_exit_code,
             watch,
+            follow,
             unwatch,
+            watch_directory,
+            unwatch_directory,
             message,
             show_open_dialog,
             show_save_dialog,
@@ -285,8 +461,18 @@ pub fn run() {
             change_theme,
             run_grep,
             abort_grep,
+            run_replace,
+            apply_replace,
             change_encoding,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(move |_, event| {
+            if let tauri::RunEvent::Exit = event {
+                if report_closed {
+                    session::report_closed(helper::exit_code());
+                }
+                std::process::exit(helper::exit_code());
+            }
+        });
 }
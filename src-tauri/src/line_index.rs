@@ -0,0 +1,273 @@
+use crate::helper;
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+use tauri::{Emitter, EventTarget};
+
+static INDEXES: Mutex<BTreeMap<String, IndexEntry>> = Mutex::new(BTreeMap::new());
+const LINE_INDEX_EVENT_NAME: &str = "line_index_event";
+/// Byte offset of every Nth line is kept
+const INTERVAL: u64 = 1000;
+const SCAN_BUFFER_LENGTH: usize = 1024 * 1024;
+/// Most lines read_lines returns at once
+const MAX_READ_LINES: u64 = 10_000;
+
+type IndexEntry = Arc<OnceLock<Result<Arc<LineIndex>, String>>>;
+
+pub struct LineIndex {
+    pub encoding: &'static Encoding,
+    pub bom_length: u64,
+    pub size: u64,
+    pub line_count: u64,
+    modified: Option<SystemTime>,
+    /// offsets[n] is the byte offset of line n * INTERVAL + 1
+    offsets: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LineIndexEvent {
+    file_path: String,
+    line_count: u64,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineQuery {
+    file_path: String,
+    line: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OffsetQuery {
+    file_path: String,
+    offset: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineRangeRequest {
+    file_path: String,
+    start_line: u64,
+    count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineRange {
+    start_line: u64,
+    end_line: u64,
+    start_offset: u64,
+    end_offset: u64,
+    content: String,
+}
+
+/// Builds the index on a background thread and notifies the window when it is ready
+pub fn build(window: &tauri::WebviewWindow, file_path: String) {
+    let window = window.clone();
+    std::thread::spawn(move || {
+        let (line_count, error) = match get(&file_path) {
+            Ok(index) => (index.line_count, None),
+            Err(e) => (0, Some(e)),
+        };
+        let _ = window.emit_to(
+            EventTarget::WebviewWindow {
+                label: window.label().to_string(),
+            },
+            LINE_INDEX_EVENT_NAME,
+            LineIndexEvent {
+                file_path,
+                line_count,
+                error,
+            },
+        );
+    });
+}
+
+/// Returns the index for the file, building it first if it is missing or outdated.
+/// Concurrent callers for the same file wait for a single build.
+pub fn get(file_path: &str) -> Result<Arc<LineIndex>, String> {
+    let entry = {
+        let mut indexes = INDEXES.lock().unwrap();
+        let entry = indexes.entry(file_path.to_string()).or_default();
+        let outdated = match entry.get() {
+            Some(Ok(index)) => !index.is_fresh(file_path),
+            Some(Err(_)) => true,
+            None => false,
+        };
+        if outdated {
+            *entry = IndexEntry::default();
+        }
+        entry.clone()
+    };
+
+    entry.get_or_init(|| create(file_path).map(Arc::new)).clone()
+}
+
+pub fn line_to_offset(query: LineQuery) -> Result<u64, String> {
+    let index = get(&query.file_path)?;
+    let mut file = File::open(&query.file_path).map_err(|e| e.to_string())?;
+    index.line_to_offset(&mut file, query.line)
+}
+
+pub fn offset_to_line(query: OffsetQuery) -> Result<u64, String> {
+    let index = get(&query.file_path)?;
+    let mut file = File::open(&query.file_path).map_err(|e| e.to_string())?;
+    index.offset_to_line(&mut file, query.offset)
+}
+
+pub fn read_lines(request: LineRangeRequest) -> Result<LineRange, String> {
+    let index = get(&request.file_path)?;
+    let mut file = File::open(&request.file_path).map_err(|e| e.to_string())?;
+
+    let start_line = request.start_line.clamp(1, index.line_count);
+    let end_line = (start_line + request.count.clamp(1, MAX_READ_LINES) - 1).min(index.line_count);
+    let start_offset = index.line_to_offset(&mut file, start_line)?;
+    let end_offset = if end_line == index.line_count {
+        index.size
+    } else {
+        index.line_to_offset(&mut file, end_line + 1)?
+    };
+
+    let mut bytes = vec![0; (end_offset - start_offset) as usize];
+    file.seek(SeekFrom::Start(start_offset)).map_err(|e| e.to_string())?;
+    file.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+
+    Ok(LineRange {
+        start_line,
+        end_line,
+        start_offset,
+        end_offset,
+        content: index.encoding.decode_without_bom_handling(&bytes).0.to_string(),
+    })
+}
+
+fn create(file_path: &str) -> Result<LineIndex, String> {
+    let mut file = File::open(file_path).map_err(|e| e.to_string())?;
+    let metadata = file.metadata().map_err(|e| e.to_string())?;
+    let size = metadata.len();
+
+    let (encoding, bom_length) = helper::detect_file_encoding(&mut file, size)?;
+    let bom_length = bom_length as u64;
+
+    let mut offsets = vec![bom_length];
+    let mut line_count = 1;
+    scan_lines(&mut file, encoding, bom_length, |line_start| {
+        if line_count % INTERVAL == 0 {
+            offsets.push(line_start);
+        }
+        line_count += 1;
+        true
+    })?;
+
+    Ok(LineIndex {
+        encoding,
+        bom_length,
+        size,
+        line_count,
+        modified: metadata.modified().ok(),
+        offsets,
+    })
+}
+
+impl LineIndex {
+    fn is_fresh(&self, file_path: &str) -> bool {
+        match std::fs::metadata(file_path) {
+            Ok(metadata) => metadata.len() == self.size && metadata.modified().ok() == self.modified,
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the byte offset where the 1-based line starts
+    pub fn line_to_offset(&self, file: &mut File, line: u64) -> Result<u64, String> {
+        if line == 0 || line > self.line_count {
+            return Err(format!("Line {} is out of range", line));
+        }
+
+        let checkpoint = (line - 1) / INTERVAL;
+        let mut remaining = (line - 1) % INTERVAL;
+        let mut offset = self.offsets[checkpoint as usize];
+        if remaining == 0 {
+            return Ok(offset);
+        }
+
+        scan_lines(file, self.encoding, offset, |line_start| {
+            offset = line_start;
+            remaining -= 1;
+            remaining > 0
+        })?;
+
+        Ok(offset)
+    }
+
+    /// Returns the 1-based line containing the byte offset
+    pub fn offset_to_line(&self, file: &mut File, offset: u64) -> Result<u64, String> {
+        if offset > self.size {
+            return Err(format!("Offset {} is out of range", offset));
+        }
+
+        let checkpoint = self.offsets.partition_point(|start| *start <= offset).max(1) - 1;
+        let mut line = checkpoint as u64 * INTERVAL + 1;
+        scan_lines(file, self.encoding, self.offsets[checkpoint], |line_start| {
+            if line_start > offset {
+                return false;
+            }
+            line += 1;
+            true
+        })?;
+
+        Ok(line)
+    }
+}
+
+/// Calls f with the offset following each line break after from, until f returns false.
+/// from must be at a line start so UTF-16 code units stay aligned.
+/// Only LF ends a line, so a file with CR-only line endings is indexed as a single line.
+fn scan_lines(file: &mut File, encoding: &'static Encoding, from: u64, mut f: impl FnMut(u64) -> bool) -> Result<(), String> {
+    file.seek(SeekFrom::Start(from)).map_err(|e| e.to_string())?;
+
+    let utf16_newline: Option<&[u8]> = if encoding == encoding_rs::UTF_16LE {
+        Some(b"\n\0")
+    } else if encoding == encoding_rs::UTF_16BE {
+        Some(b"\0\n")
+    } else {
+        None
+    };
+
+    let mut buffer = vec![0; SCAN_BUFFER_LENGTH];
+    let mut position = from;
+    loop {
+        let read = read_full(file, &mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+
+        let bytes = &buffer[..read];
+        // Buffers are filled completely except the last one, so code units never straddle them
+        let proceed = match utf16_newline {
+            Some(newline) => bytes.chunks_exact(2).enumerate().filter(|(_, unit)| *unit == newline).all(|(index, _)| f(position + index as u64 * 2 + 2)),
+            None => memchr::memchr_iter(b'\n', bytes).all(|index| f(position + index as u64 + 1)),
+        };
+        if !proceed {
+            return Ok(());
+        }
+
+        position += read as u64;
+    }
+}
+
+fn read_full(file: &mut File, buffer: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(filled)
+}
@@ -1,3 +1,7 @@
+#[cfg(target_os = "linux")]
+use crossbeam_channel::RecvTimeoutError;
+#[cfg(target_os = "linux")]
+use std::{path::Path, time::Duration};
 #[cfg(target_os = "windows")]
 use windows::{
     core::PCWSTR,
@@ -7,7 +11,19 @@ use windows::{
     },
 };
 #[cfg(target_os = "linux")]
-use zbus::blocking::{connection::Builder, Connection};
+use zbus::{
+    blocking::{connection::Builder, fdo::DBusProxy, Connection, MessageIterator},
+    message::Type,
+    MatchRule,
+};
+
+#[cfg(target_os = "linux")]
+const OBJECT_PATH: &str = "/org/editor/Session";
+#[cfg(target_os = "linux")]
+const INTERFACE: &str = "org.editor.Session";
+
+#[cfg(target_os = "linux")]
+const WINDOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct Session {
     #[cfg(target_os = "linux")]
@@ -24,12 +40,63 @@ fn encode_wide(string: impl AsRef<std::ffi::OsStr>) -> Vec<u16> {
     string.as_ref().encode_wide().chain(std::iter::once(0)).collect()
 }
 
-pub fn start(id: &str) -> Result<Session, String> {
-    let id = if cfg!(target_os = "linux") {
+fn session_name(id: &str) -> String {
+    if cfg!(target_os = "linux") {
         format!("org.{}.session", id.replace(['.', '-'], "_"))
     } else {
         id.to_string()
-    };
+    }
+}
+
+#[cfg(target_os = "linux")]
+type WindowOpener = Box<dyn Fn(&str, &[String]) -> Result<u32, String> + Send + Sync>;
+
+#[cfg(target_os = "linux")]
+struct Forwarder {
+    open_window: WindowOpener,
+}
+
+#[cfg(target_os = "linux")]
+impl Forwarder {
+    fn spawn(&self, current_dir: &str, args: Vec<String>) -> zbus::fdo::Result<u32> {
+        let args: Vec<String> = std::iter::once(crate::cli::FORWARDED.to_string()).chain(args).collect();
+        (self.open_window)(current_dir, &args).map_err(zbus::fdo::Error::Failed)
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[zbus::interface(name = "org.editor.Session")]
+impl Forwarder {
+    /// Opens a new window with args. Relative paths in args are resolved against current_dir.
+    fn open(&self, current_dir: String, args: Vec<String>) -> zbus::fdo::Result<()> {
+        self.spawn(&current_dir, args).map(|_| ())
+    }
+
+    /// Same as open, and returns the process id of the window.
+    /// The window itself emits Closed with that id and its exit code, so this instance can exit before it.
+    fn open_and_wait(&self, current_dir: String, args: Vec<String>) -> zbus::fdo::Result<u32> {
+        self.spawn(&current_dir, args)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn serve(builder: Builder, name: &str, open_window: WindowOpener) -> zbus::Result<Connection> {
+    builder
+        .name(name)?
+        .serve_at(
+            OBJECT_PATH,
+            Forwarder {
+                open_window,
+            },
+        )?
+        .replace_existing_names(false)
+        .allow_name_replacements(false)
+        .build()
+}
+
+#[allow(unused_variables)]
+pub fn start(id: &str, app: &tauri::AppHandle) -> Result<Session, String> {
+    let id = session_name(id);
     #[cfg(target_os = "windows")]
     unsafe {
         let mutex_name = encode_wide(&id);
@@ -45,7 +112,10 @@ pub fn start(id: &str) -> Result<Session, String> {
 
     #[cfg(target_os = "linux")]
     {
-        if let Ok(connection) = Builder::session().unwrap().name(&id).unwrap().replace_existing_names(false).allow_name_replacements(false).build() {
+        let app = app.clone();
+        let open_window: WindowOpener = Box::new(move |current_dir, args| crate::helper::open_window(&app, args, Some(current_dir)));
+        let connection = Builder::session().and_then(|builder| serve(builder, &id, open_window));
+        if let Ok(connection) = connection {
             Ok(Session {
                 connection,
                 id,
@@ -64,5 +134,261 @@ pub fn end(session: &Session) {
     }
 
     #[cfg(target_os = "linux")]
-    let _ = session.connection.release_name(&session.id);
+    let _ = session.connection.release_name(session.id.as_str());
+}
+
+/// Hands args over to the instance that owns the session and returns the exit code for this process.
+/// With wait, blocks until the window opened for args closes and returns its exit code.
+/// Returns None when there is no such instance, or on Windows where the session is only a mutex.
+#[allow(unused_variables)]
+pub fn forward(id: &str, args: &[String], wait: bool) -> Option<i32> {
+    #[cfg(target_os = "windows")]
+    {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let connection = Connection::session().ok()?;
+        forward_to(&connection, &session_name(id), args, wait)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn forward_to(connection: &Connection, destination: &str, args: &[String], wait: bool) -> Option<i32> {
+    let current_dir = std::env::current_dir().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+    let body = (current_dir, args.to_vec());
+
+    if !wait {
+        connection.call_method(Some(destination), OBJECT_PATH, Some(INTERFACE), "Open", &body).ok()?;
+        return Some(0);
+    }
+
+    /* Subscribe before opening so Closed cannot be missed */
+    let dbus = DBusProxy::new(connection).ok()?;
+    let closed = MatchRule::builder().msg_type(Type::Signal).path(OBJECT_PATH).ok()?.interface(INTERFACE).ok()?.member("Closed").ok()?.build();
+    dbus.add_match_rule(closed).ok()?;
+    let messages = MessageIterator::from(connection);
+
+    let reply = connection.call_method(Some(destination), OBJECT_PATH, Some(INTERFACE), "OpenAndWait", &body).ok()?;
+    /* The window is open from here on, so failures must not make the caller open another one */
+    Some(wait_closed(messages, &reply).unwrap_or_else(|e| {
+        eprintln!("[FS_ERR] Failed to wait for the window: {}", e);
+        1
+    }))
+}
+
+/// Waits for the window whose process id is in reply to emit Closed.
+/// A window that dies without emitting it is noticed once its process is gone.
+#[cfg(target_os = "linux")]
+fn wait_closed(messages: MessageIterator, reply: &zbus::Message) -> zbus::Result<i32> {
+    let pid: u32 = reply.body().deserialize()?;
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        for message in messages.flatten() {
+            if message.header().member().is_none_or(|member| member.as_str() != "Closed") {
+                continue;
+            }
+            if let Ok((closed, code)) = message.body().deserialize::<(u32, i32)>() {
+                if closed == pid {
+                    let _ = tx.send(code);
+                    return;
+                }
+            }
+        }
+    });
+
+    let process = format!("/proc/{}", pid);
+    loop {
+        match rx.recv_timeout(WINDOW_POLL_INTERVAL) {
+            Ok(code) => return Ok(code),
+            Err(RecvTimeoutError::Timeout) if Path::new(&process).exists() => {}
+            /* Closed is emitted just before the window exits, so it may still be on its way */
+            Err(RecvTimeoutError::Timeout) => return Ok(rx.recv_timeout(WINDOW_POLL_INTERVAL).unwrap_or(1)),
+            Err(RecvTimeoutError::Disconnected) => return Ok(1),
+        }
+    }
+}
+
+pub fn report_closed(code: i32) {
+    #[cfg(target_os = "linux")]
+    if let Err(e) = Connection::session().and_then(|connection| emit_closed(&connection, std::process::id(), code)) {
+        eprintln!("[FS_ERR] Failed to emit Closed: {}", e);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn emit_closed(connection: &Connection, pid: u32, code: i32) -> zbus::Result<()> {
+    connection.emit_signal(None::<&str>, OBJECT_PATH, INTERFACE, "Closed", &(pid, code))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::mpsc,
+    };
+
+    const NAME: &str = "org.editor_test.session";
+
+    /// A dbus-daemon of its own, so tests neither need nor disturb the session bus
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon").args(["--session", "--nofork", "--print-address"]).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        fn builder(&self) -> Builder<'static> {
+            Builder::address(self.address.as_str()).unwrap()
+        }
+
+        fn connect(&self) -> Connection {
+            self.builder().build().unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// A missing dbus-daemon fails the test unless SKIP_DBUS_TESTS is set, so these tests cannot pass without running
+    fn private_bus() -> Option<PrivateBus> {
+        let bus = PrivateBus::start();
+        if bus.is_none() {
+            assert!(std::env::var_os("SKIP_DBUS_TESTS").is_some(), "dbus-daemon is not available. Set SKIP_DBUS_TESTS to skip the session tests.");
+            eprintln!("dbus-daemon is not available, skipped");
+        }
+        bus
+    }
+
+    fn spawn(command: &str) -> Result<u32, String> {
+        let mut child = Command::new("sh").args(["-c", command]).spawn().map_err(|e| e.to_string())?;
+        let pid = child.id();
+        std::thread::spawn(move || child.wait());
+        Ok(pid)
+    }
+
+    /// Opens windows that stay up until release is signalled, then report Closed with code and exit, as a window started with --wait does
+    fn reporting_opener(address: String, code: i32, release: crossbeam_channel::Receiver<()>) -> WindowOpener {
+        Box::new(move |_, _| {
+            let mut window = Command::new("sleep").arg("60").spawn().map_err(|e| e.to_string())?;
+            let pid = window.id();
+            let (address, release) = (address.clone(), release.clone());
+            std::thread::spawn(move || {
+                let _ = release.recv();
+                let connection = Builder::address(address.as_str()).unwrap().build().unwrap();
+                emit_closed(&connection, pid, code).unwrap();
+                let _ = window.kill();
+                let _ = window.wait();
+            });
+            Ok(pid)
+        })
+    }
+
+    #[test]
+    fn open_passes_args_and_directory() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let open_window: WindowOpener = Box::new(move |current_dir, args| {
+            tx.send((current_dir.to_string(), args.to_vec())).unwrap();
+            spawn("true")
+        });
+        let _primary = serve(bus.builder(), NAME, open_window).unwrap();
+
+        let args = vec!["a.txt:3".to_string(), "--readonly".to_string()];
+        assert_eq!(forward_to(&bus.connect(), NAME, &args, false), Some(0));
+
+        let (current_dir, opened) = rx.recv().unwrap();
+        assert_eq!(current_dir, std::env::current_dir().unwrap().to_string_lossy());
+        assert_eq!(opened, vec![crate::cli::FORWARDED, "a.txt:3", "--readonly"]);
+    }
+
+    #[test]
+    fn open_and_wait_returns_exit_code_of_window() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+        let (release, released) = crossbeam_channel::unbounded();
+        release.send(()).unwrap();
+        let _primary = serve(bus.builder(), NAME, reporting_opener(bus.address.clone(), 3, released)).unwrap();
+
+        assert_eq!(forward_to(&bus.connect(), NAME, &["--wait".to_string()], true), Some(3));
+    }
+
+    #[test]
+    fn open_and_wait_outlives_running_instance() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+        let (release, released) = crossbeam_channel::unbounded();
+        let primary = serve(bus.builder(), NAME, reporting_opener(bus.address.clone(), 4, released)).unwrap();
+
+        // The steps of forward_to, so the running instance is gone before the window reports
+        let connection = bus.connect();
+        let closed = MatchRule::builder().msg_type(Type::Signal).path(OBJECT_PATH).unwrap().interface(INTERFACE).unwrap().member("Closed").unwrap().build();
+        DBusProxy::new(&connection).unwrap().add_match_rule(closed).unwrap();
+        let messages = MessageIterator::from(&connection);
+        let reply = connection.call_method(Some(NAME), OBJECT_PATH, Some(INTERFACE), "OpenAndWait", &(String::new(), vec!["--wait".to_string()])).unwrap();
+
+        drop(primary);
+        release.send(()).unwrap();
+        assert_eq!(wait_closed(messages, &reply).unwrap(), 4);
+    }
+
+    #[test]
+    fn open_and_wait_ends_when_window_dies_without_reporting() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+        let _primary = serve(bus.builder(), NAME, Box::new(|_, _| spawn("sleep 0.2"))).unwrap();
+
+        assert_eq!(forward_to(&bus.connect(), NAME, &["--wait".to_string()], true), Some(1));
+    }
+
+    #[test]
+    fn open_fails_without_running_instance() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+
+        assert_eq!(forward_to(&bus.connect(), NAME, &[], false), None);
+        assert_eq!(forward_to(&bus.connect(), NAME, &[], true), None);
+    }
+
+    #[test]
+    fn only_one_instance_owns_session() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+        let _primary = serve(bus.builder(), NAME, Box::new(|_, _| spawn("true"))).unwrap();
+
+        assert!(serve(bus.builder(), NAME, Box::new(|_, _| spawn("true"))).is_err());
+    }
+
+    #[test]
+    fn open_reports_failure_to_start_window() {
+        let Some(bus) = private_bus() else {
+            return;
+        };
+        let _primary = serve(bus.builder(), NAME, Box::new(|_, _| Err("no binary".to_string()))).unwrap();
+
+        assert_eq!(forward_to(&bus.connect(), NAME, &[], false), None);
+    }
 }
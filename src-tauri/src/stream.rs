@@ -0,0 +1,155 @@
+use crate::line_index;
+use encoding_rs::{CoderResult, Decoder, Encoding};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+static STREAMS: Mutex<BTreeMap<u32, Stream>> = Mutex::new(BTreeMap::new());
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
+const ALIGN_LENGTH: usize = 64 * 1024;
+/// Upper bound of a chunk so a bogus len cannot allocate the whole file at once
+const MAX_CHUNK_LENGTH: u64 = 16 * 1024 * 1024;
+
+struct Stream {
+    file: File,
+    encoding: &'static Encoding,
+    decoder: Decoder,
+    bom_length: u64,
+    size: u64,
+    /// Offset the decoder expects next. Reading from here keeps characters split across chunks intact.
+    next_offset: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamInfo {
+    handle: u32,
+    size: u64,
+    encoding: String,
+    bom: bool,
+    line_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkRequest {
+    handle: u32,
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    content: String,
+    /// Actual start of the chunk. It can be after the requested offset when that was in the middle of a character.
+    offset: u64,
+    next_offset: u64,
+    eof: bool,
+}
+
+pub fn open(file_path: &str) -> Result<StreamInfo, String> {
+    let file = File::open(file_path).map_err(|e| e.to_string())?;
+    let index = line_index::get(file_path)?;
+    let encoding = index.encoding;
+    let bom_length = index.bom_length;
+    let size = index.size;
+
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    let stream = Stream {
+        file,
+        encoding,
+        decoder: encoding.new_decoder_without_bom_handling(),
+        bom_length,
+        size,
+        next_offset: bom_length,
+    };
+    STREAMS.lock().unwrap().insert(handle, stream);
+
+    Ok(StreamInfo {
+        handle,
+        size,
+        encoding: encoding.name().to_string(),
+        bom: bom_length > 0,
+        line_count: index.line_count,
+    })
+}
+
+pub fn read_chunk(request: ChunkRequest) -> Result<Chunk, String> {
+    let mut streams = STREAMS.lock().unwrap();
+    let stream = streams.get_mut(&request.handle).ok_or("Stream is not opened".to_string())?;
+
+    let mut offset = request.offset.max(stream.bom_length).min(stream.size);
+    if offset != stream.next_offset {
+        // Random access, so the decoder state no longer applies
+        stream.decoder = stream.encoding.new_decoder_without_bom_handling();
+        offset = align_offset(stream, offset)?;
+    }
+
+    let len = request.len.min(MAX_CHUNK_LENGTH).min(stream.size - offset);
+    let mut bytes = vec![0; len as usize];
+    stream.file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    stream.file.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+
+    let next_offset = offset + len;
+    let eof = next_offset >= stream.size;
+    let mut content = String::with_capacity(stream.decoder.max_utf8_buffer_length(bytes.len()).unwrap_or(bytes.len()));
+    let mut position = 0;
+    loop {
+        let (result, read, _) = stream.decoder.decode_to_string(&bytes[position..], &mut content, eof);
+        position += read;
+        match result {
+            CoderResult::InputEmpty => break,
+            CoderResult::OutputFull => {
+                let remaining = bytes.len() - position;
+                content.reserve(stream.decoder.max_utf8_buffer_length(remaining).unwrap_or(remaining).max(4));
+            }
+        }
+    }
+    stream.next_offset = next_offset;
+
+    Ok(Chunk {
+        content,
+        offset,
+        next_offset,
+        eof,
+    })
+}
+
+pub fn close(handle: u32) {
+    STREAMS.lock().unwrap().remove(&handle);
+}
+
+/// Moves offset forward to the nearest position where decoding can start cleanly
+fn align_offset(stream: &mut Stream, offset: u64) -> Result<u64, String> {
+    // Every byte of a single-byte encoding is a character boundary
+    if offset == stream.bom_length || offset >= stream.size || stream.encoding.is_single_byte() {
+        return Ok(offset);
+    }
+
+    if stream.encoding == encoding_rs::UTF_16LE || stream.encoding == encoding_rs::UTF_16BE {
+        let aligned = offset + (offset - stream.bom_length) % 2;
+        return Ok(aligned.min(stream.size));
+    }
+
+    // Look back one byte so a line break right before offset is recognized
+    let mut bytes = vec![0; (stream.size - offset + 1).min(ALIGN_LENGTH as u64) as usize];
+    stream.file.seek(SeekFrom::Start(offset - 1)).map_err(|e| e.to_string())?;
+    stream.file.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+
+    let skip = if stream.encoding == encoding_rs::UTF_8 {
+        // Skip continuation bytes
+        bytes[1..].iter().position(|byte| byte & 0b1100_0000 != 0b1000_0000)
+    } else if bytes[0] == b'\n' {
+        Some(0)
+    } else {
+        // Other multibyte encodings are not self-synchronizing, so start from the next line
+        memchr::memchr(b'\n', &bytes[1..]).map(|index| index + 1)
+    };
+
+    Ok(skip.map(|skip| offset + skip as u64).unwrap_or(offset))
+}
@@ -1,28 +1,201 @@
 use crate::helper;
 use crossbeam_channel::{bounded, Receiver, Sender};
+use encoding_rs::Decoder;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify_debouncer_full::{
     new_debouncer,
-    notify::{event::ModifyKind, EventKind, RecursiveMode},
-    DebouncedEvent,
+    notify::{
+        event::{ModifyKind, RenameMode},
+        EventKind, RecommendedWatcher, RecursiveMode,
+    },
+    DebouncedEvent, Debouncer, RecommendedCache,
 };
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tauri::{AppHandle, Emitter};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tauri::{AppHandle, Emitter, EventTarget};
 
 const WATCH_EVENT_NAME: &str = "watch_event";
+const WATCH_APPEND_EVENT_NAME: &str = "watch_append";
+const WATCH_DIRECTORY_EVENT_NAME: &str = "watch_directory_event";
+
+type FileWatcher = Debouncer<RecommendedWatcher, RecommendedCache>;
 
 pub struct WatchTx(pub Sender<WatcherCommand>);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchEventKind {
+    #[default]
+    Modified,
+    Removed,
+    Renamed,
+    Recreated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct WatchEvent {
+    pub kind: WatchEventKind,
     pub file_path: String,
+    /// Path before the rename when kind is renamed
+    pub old_path: Option<String>,
     pub content: String,
     pub encoding: String,
+    pub bom: bool,
+    pub eol: String,
+    pub binary: bool,
+    pub invalid_ranges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchAppendEvent {
+    pub file_path: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryWatchRequest {
+    pub path: String,
+    pub recursive: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryChangeKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DirectoryChange {
+    pub kind: DirectoryChangeKind,
+    pub path: String,
+    pub old_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryWatchEvent {
+    pub directory: String,
+    pub changes: Vec<DirectoryChange>,
 }
 
 pub enum WatcherCommand {
-    Watch(String),
-    Unwatch(String),
+    Watch {
+        path: String,
+        label: String,
+    },
+    Follow {
+        path: String,
+        label: String,
+    },
+    Unwatch {
+        path: String,
+        label: String,
+    },
+    WatchDirectory {
+        path: String,
+        label: String,
+        recursive: bool,
+        include: GlobSet,
+        exclude: GlobSet,
+    },
+    UnwatchDirectory {
+        path: String,
+        label: String,
+    },
+    UnwatchWindow(String),
+}
+
+impl WatcherCommand {
+    pub fn watch_directory(request: DirectoryWatchRequest, label: String) -> Result<Self, String> {
+        Ok(Self::WatchDirectory {
+            path: request.path,
+            label,
+            recursive: request.recursive,
+            include: build_globset(&request.include)?,
+            exclude: build_globset(&request.exclude)?,
+        })
+    }
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Read position of a followed file, so only appended bytes are sent
+struct FollowState {
+    offset: u64,
+    file_id: Option<u64>,
+    decoder: Decoder,
+}
+
+impl FollowState {
+    fn new(file_path: &Path) -> Result<Self, String> {
+        let mut file = File::open(file_path).map_err(|e| e.to_string())?;
+        let metadata = file.metadata().map_err(|e| e.to_string())?;
+        let (encoding, _) = helper::detect_file_encoding(&mut file, metadata.len())?;
+        Ok(Self {
+            offset: metadata.len(),
+            file_id: get_file_id(&metadata),
+            decoder: encoding.new_decoder_without_bom_handling(),
+        })
+    }
+
+    /// Used when the file cannot be read yet. The next change is taken as a truncation, so the file is reloaded and followed from there.
+    fn stale() -> Self {
+        Self {
+            offset: u64::MAX,
+            file_id: None,
+            decoder: encoding_rs::UTF_8.new_decoder_without_bom_handling(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Subscription {
+    count: usize,
+    follow: bool,
+}
+
+struct DirectorySubscription {
+    count: usize,
+    recursive: bool,
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl DirectorySubscription {
+    fn is_match(&self, directory: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(directory) else {
+            return false;
+        };
+        if relative.as_os_str().is_empty() || (!self.recursive && path.parent() != Some(directory)) {
+            return false;
+        }
+        (self.include.is_empty() || self.include.is_match(relative)) && !self.exclude.is_match(relative)
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    subscribers: HashMap<PathBuf, HashMap<String, Subscription>>,
+    followed: HashMap<PathBuf, FollowState>,
+    /// Watched files that disappeared. Their parent directories are watched until they come back.
+    missing: HashSet<PathBuf>,
+    directories: HashMap<PathBuf, HashMap<String, DirectorySubscription>>,
 }
 
 pub fn spwan_watcher(app_handle: &AppHandle, cmd_rx: Receiver<WatcherCommand>) -> Result<(), String> {
@@ -31,16 +204,35 @@ pub fn spwan_watcher(app_handle: &AppHandle, cmd_rx: Receiver<WatcherCommand>) -
     let mut watcher = new_debouncer(Duration::from_millis(100), None, move |res| tx.send(res).unwrap_or_default()).map_err(|e| e.to_string())?;
     let app_handle = app_handle.clone();
     tauri::async_runtime::spawn(async move {
+        let mut state = WatchState::default();
         loop {
             crossbeam_channel::select! {
                 recv(cmd_rx) -> cmd  => {
                     if let Ok(cmd) = cmd {
                         match cmd {
-                            WatcherCommand::Watch(path) => {
-                                let _ = watcher.watch(path, RecursiveMode::NonRecursive);
+                            WatcherCommand::Watch { path, label } => {
+                                state.subscribe(&mut watcher, PathBuf::from(path), label, false);
+                            },
+                            WatcherCommand::Follow { path, label } => {
+                                state.subscribe(&mut watcher, PathBuf::from(path), label, true);
+                            },
+                            WatcherCommand::Unwatch { path, label } => {
+                                state.unsubscribe(&mut watcher, Path::new(&path), &label);
+                            },
+                            WatcherCommand::WatchDirectory { path, label, recursive, include, exclude } => {
+                                let subscription = DirectorySubscription {
+                                    count: 1,
+                                    recursive,
+                                    include,
+                                    exclude,
+                                };
+                                state.subscribe_directory(&mut watcher, PathBuf::from(path), label, subscription);
+                            },
+                            WatcherCommand::UnwatchDirectory { path, label } => {
+                                state.unsubscribe_directory(&mut watcher, Path::new(&path), &label);
                             },
-                            WatcherCommand::Unwatch(path) => {
-                                let _ = watcher.unwatch(path);
+                            WatcherCommand::UnwatchWindow(label) => {
+                                state.unsubscribe_window(&mut watcher, &label);
                             }
                         }
                     }else {
@@ -53,11 +245,14 @@ pub fn spwan_watcher(app_handle: &AppHandle, cmd_rx: Receiver<WatcherCommand>) -
                     if let Ok(event_result) = event_result {
                         match event_result {
                             Ok(events) => {
-                                for event in events {
-                                    if is_modified(event.kind) {
-                                       handle_event(&app_handle, &event).unwrap();
+                                for event in &events {
+                                    if let Err(e) = state.handle(&app_handle, &mut watcher, event) {
+                                        eprintln!("[FS_ERR] Watch event error: {:?}", e);
                                     }
                                 }
+                                if let Err(e) = state.handle_directories(&app_handle, &events) {
+                                    eprintln!("[FS_ERR] Watch event error: {:?}", e);
+                                }
                             },
                             Err(errors) => {
                                 for error in errors {
@@ -74,18 +269,409 @@ pub fn spwan_watcher(app_handle: &AppHandle, cmd_rx: Receiver<WatcherCommand>) -
     Ok(())
 }
 
-fn handle_event(app: &AppHandle, event: &DebouncedEvent) -> Result<(), String> {
-    let result = helper::read_to_string(event.paths[0].to_str().unwrap()).map_err(|e| notify_debouncer_full::notify::Error::generic(&e)).map_err(|e| e.to_string())?;
+impl WatchState {
+    fn subscribe(&mut self, watcher: &mut FileWatcher, path: PathBuf, label: String, follow: bool) {
+        if follow && !self.followed.contains_key(&path) {
+            self.followed.insert(path.clone(), FollowState::new(&path).unwrap_or_else(|_| FollowState::stale()));
+        }
+
+        let windows = self.subscribers.entry(path.clone()).or_default();
+        if windows.is_empty() {
+            let _ = watcher.watch(&path, RecursiveMode::NonRecursive);
+        }
+        let subscription = windows.entry(label).or_default();
+        subscription.count += 1;
+        subscription.follow |= follow;
+    }
+
+    fn unsubscribe(&mut self, watcher: &mut FileWatcher, path: &Path, label: &str) {
+        let Some(windows) = self.subscribers.get_mut(path) else {
+            return;
+        };
+
+        if let Some(subscription) = windows.get_mut(label) {
+            subscription.count -= 1;
+            if subscription.count == 0 {
+                windows.remove(label);
+            }
+        }
+
+        if windows.is_empty() {
+            self.forget(watcher, path);
+        }
+    }
+
+    fn subscribe_directory(&mut self, watcher: &mut FileWatcher, path: PathBuf, label: String, subscription: DirectorySubscription) {
+        let subscriptions = self.directories.entry(path.clone()).or_default();
+        let watching = !subscriptions.is_empty();
+        let was_recursive = subscriptions.values().any(|subscription| subscription.recursive);
+
+        let count = subscriptions.get(&label).map(|existing| existing.count).unwrap_or_default();
+        subscriptions.insert(
+            label,
+            DirectorySubscription {
+                count: count + 1,
+                ..subscription
+            },
+        );
+
+        let recursive = subscriptions.values().any(|subscription| subscription.recursive);
+        if !watching || was_recursive != recursive {
+            let _ = watcher.unwatch(&path);
+            let _ = watcher.watch(&path, get_recursive_mode(recursive));
+        }
+    }
+
+    fn unsubscribe_directory(&mut self, watcher: &mut FileWatcher, path: &Path, label: &str) {
+        let Some(subscriptions) = self.directories.get_mut(path) else {
+            return;
+        };
+
+        if let Some(subscription) = subscriptions.get_mut(label) {
+            subscription.count -= 1;
+            if subscription.count == 0 {
+                subscriptions.remove(label);
+            }
+        }
+
+        self.rewatch_directory(watcher, path);
+    }
+
+    fn rewatch_directory(&mut self, watcher: &mut FileWatcher, path: &Path) {
+        let Some(subscriptions) = self.directories.get(path) else {
+            return;
+        };
+
+        let _ = watcher.unwatch(path);
+        if subscriptions.is_empty() {
+            self.directories.remove(path);
+            // A missing file may still rely on this directory being watched
+            if self.missing.iter().any(|missing| missing.parent() == Some(path)) {
+                let _ = watcher.watch(path, RecursiveMode::NonRecursive);
+            }
+        } else {
+            let recursive = subscriptions.values().any(|subscription| subscription.recursive);
+            let _ = watcher.watch(path, get_recursive_mode(recursive));
+        }
+    }
+
+    fn is_directory_watched(&self, path: &Path) -> bool {
+        self.directories.iter().any(|(directory, subscriptions)| path == directory || (path.starts_with(directory) && subscriptions.values().any(|subscription| subscription.recursive)))
+    }
+
+    fn unsubscribe_window(&mut self, watcher: &mut FileWatcher, label: &str) {
+        let directories: Vec<PathBuf> = self.directories.iter_mut().filter_map(|(path, subscriptions)| subscriptions.remove(label).map(|_| path.clone())).collect();
+        for directory in directories {
+            self.rewatch_directory(watcher, &directory);
+        }
+
+        let paths: Vec<PathBuf> = self
+            .subscribers
+            .iter_mut()
+            .filter_map(|(path, windows)| {
+                windows.remove(label);
+                if windows.is_empty() {
+                    Some(path.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for path in paths {
+            self.forget(watcher, &path);
+        }
+    }
+
+    fn forget(&mut self, watcher: &mut FileWatcher, path: &Path) {
+        self.subscribers.remove(path);
+        self.followed.remove(path);
+        if self.missing.remove(path) {
+            self.unwatch_parent(watcher, path);
+        } else {
+            let _ = watcher.unwatch(path);
+        }
+    }
+
+    fn labels(&self, path: &Path, filter: impl Fn(&Subscription) -> bool) -> Vec<String> {
+        match self.subscribers.get(path) {
+            Some(windows) => windows.iter().filter(|(_, subscription)| filter(subscription)).map(|(label, _)| label.clone()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Collects the changes under each watched directory and sends them to each window in one event
+    fn handle_directories(&self, app: &AppHandle, events: &[DebouncedEvent]) -> Result<(), String> {
+        for (directory, subscriptions) in &self.directories {
+            for (label, subscription) in subscriptions {
+                let mut changes: Vec<DirectoryChange> = Vec::new();
+                for event in events {
+                    if let Some(change) = to_directory_change(event, |path| subscription.is_match(directory, path)) {
+                        if !changes.contains(&change) {
+                            changes.push(change);
+                        }
+                    }
+                }
+
+                if !changes.is_empty() {
+                    emit(
+                        app,
+                        std::slice::from_ref(label),
+                        WATCH_DIRECTORY_EVENT_NAME,
+                        DirectoryWatchEvent {
+                            directory: directory.to_string_lossy().to_string(),
+                            changes,
+                        },
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, app: &AppHandle, watcher: &mut FileWatcher, event: &DebouncedEvent) -> Result<(), String> {
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let (old_path, new_path) = (&event.paths[0], &event.paths[1]);
+                if self.subscribers.contains_key(new_path) {
+                    // Another file was renamed over the watched one, which is how atomic saves work
+                    self.handle_recreated(app, watcher, new_path)
+                } else if self.subscribers.contains_key(old_path) {
+                    if old_path.exists() {
+                        self.handle_recreated(app, watcher, old_path)
+                    } else {
+                        let labels = self.labels(old_path, |_| true);
+                        self.forget(watcher, old_path);
+                        emit(
+                            app,
+                            &labels,
+                            WATCH_EVENT_NAME,
+                            WatchEvent {
+                                kind: WatchEventKind::Renamed,
+                                file_path: new_path.to_string_lossy().to_string(),
+                                old_path: Some(old_path.to_string_lossy().to_string()),
+                                ..Default::default()
+                            },
+                        )
+                    }
+                } else {
+                    Ok(())
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(_)) | EventKind::Remove(_) => {
+                let paths: Vec<&PathBuf> = event.paths.iter().filter(|path| self.subscribers.contains_key(*path)).collect();
+                for path in paths {
+                    if path.exists() {
+                        self.handle_recreated(app, watcher, path)?;
+                    } else if !self.missing.contains(path) {
+                        self.handle_removed(app, watcher, path)?;
+                    }
+                }
+                Ok(())
+            }
+            EventKind::Create(_) => {
+                let paths: Vec<&PathBuf> = event.paths.iter().filter(|path| self.missing.contains(*path)).collect();
+                for path in paths {
+                    self.handle_recreated(app, watcher, path)?;
+                }
+                Ok(())
+            }
+            kind if is_modified(kind) => {
+                let path = &event.paths[0];
+                let followers = self.labels(path, |subscription| subscription.follow);
+                if let Some(follow_state) = self.followed.get_mut(path) {
+                    handle_append(app, &followers, path, follow_state)?;
+                }
+                let watchers = self.labels(path, |subscription| !subscription.follow);
+                handle_event(app, &watchers, path, WatchEventKind::Modified)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The file was replaced, so the old watch points to a file that no longer exists
+    fn handle_recreated(&mut self, app: &AppHandle, watcher: &mut FileWatcher, file_path: &Path) -> Result<(), String> {
+        if self.missing.remove(file_path) {
+            self.unwatch_parent(watcher, file_path);
+        }
+        let _ = watcher.unwatch(file_path);
+        watcher.watch(file_path, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+        if self.followed.contains_key(file_path) {
+            self.followed.insert(file_path.to_path_buf(), FollowState::new(file_path).unwrap_or_else(|_| FollowState::stale()));
+        }
+        handle_event(app, &self.labels(file_path, |_| true), file_path, WatchEventKind::Recreated)
+    }
+
+    fn handle_removed(&mut self, app: &AppHandle, watcher: &mut FileWatcher, file_path: &Path) -> Result<(), String> {
+        let _ = watcher.unwatch(file_path);
+        if let Some(parent) = file_path.parent() {
+            if !self.is_directory_watched(parent) {
+                let _ = watcher.watch(parent, RecursiveMode::NonRecursive);
+            }
+        }
+        self.missing.insert(file_path.to_path_buf());
+
+        emit(
+            app,
+            &self.labels(file_path, |_| true),
+            WATCH_EVENT_NAME,
+            WatchEvent {
+                kind: WatchEventKind::Removed,
+                file_path: file_path.to_string_lossy().to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn unwatch_parent(&self, watcher: &mut FileWatcher, file_path: &Path) {
+        if let Some(parent) = file_path.parent() {
+            if !self.missing.iter().any(|path| path.parent() == Some(parent)) && !self.is_directory_watched(parent) {
+                let _ = watcher.unwatch(parent);
+            }
+        }
+    }
+}
+
+fn handle_event(app: &AppHandle, labels: &[String], file_path: &Path, kind: WatchEventKind) -> Result<(), String> {
+    // Our own save, nothing changed from the editor's point of view
+    if labels.is_empty() || helper::is_self_write(file_path) {
+        return Ok(());
+    }
+
+    let result = helper::read_to_string(file_path.to_str().unwrap()).map_err(|e| notify_debouncer_full::notify::Error::generic(&e)).map_err(|e| e.to_string())?;
 
-    app.emit(
+    emit(
+        app,
+        labels,
         WATCH_EVENT_NAME,
         WatchEvent {
-            file_path: event.paths[0].to_string_lossy().to_string(),
+            kind,
+            file_path: file_path.to_string_lossy().to_string(),
+            old_path: None,
             content: result.content,
             encoding: result.encoding,
+            bom: result.bom,
+            eol: result.eol,
+            binary: result.binary,
+            invalid_ranges: result.invalid_ranges,
+        },
+    )
+}
+
+fn emit<S: Serialize + Clone>(app: &AppHandle, labels: &[String], event_name: &str, payload: S) -> Result<(), String> {
+    for label in labels {
+        app.emit_to(
+            EventTarget::WebviewWindow {
+                label: label.clone(),
+            },
+            event_name,
+            payload.clone(),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn handle_append(app: &AppHandle, labels: &[String], file_path: &Path, state: &mut FollowState) -> Result<(), String> {
+    let mut file = File::open(file_path).map_err(|e| e.to_string())?;
+    let metadata = file.metadata().map_err(|e| e.to_string())?;
+    let size = metadata.len();
+
+    // Truncated or rotated, so appended bytes cannot be told apart
+    if size < state.offset || get_file_id(&metadata) != state.file_id {
+        *state = FollowState::new(file_path).unwrap_or_else(|_| FollowState::stale());
+        return handle_event(app, labels, file_path, WatchEventKind::Modified);
+    }
+
+    if size == state.offset {
+        return Ok(());
+    }
+
+    if helper::is_self_write(file_path) {
+        state.offset = size;
+        return Ok(());
+    }
+
+    let mut bytes = vec![0; (size - state.offset) as usize];
+    file.seek(SeekFrom::Start(state.offset)).map_err(|e| e.to_string())?;
+    file.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    state.offset = size;
+
+    // Keep the decoder across appends so a character split between writes is decoded once complete
+    let mut content = String::with_capacity(state.decoder.max_utf8_buffer_length(bytes.len()).unwrap_or(bytes.len()));
+    let (_, _, _) = state.decoder.decode_to_string(&bytes, &mut content, false);
+    if content.is_empty() {
+        return Ok(());
+    }
+
+    emit(
+        app,
+        labels,
+        WATCH_APPEND_EVENT_NAME,
+        WatchAppendEvent {
+            file_path: file_path.to_string_lossy().to_string(),
+            content,
         },
     )
-    .map_err(|e| e.to_string())
+}
+
+fn to_directory_change(event: &DebouncedEvent, is_match: impl Fn(&Path) -> bool) -> Option<DirectoryChange> {
+    let change = |kind: DirectoryChangeKind, path: &Path| DirectoryChange {
+        kind,
+        path: path.to_string_lossy().to_string(),
+        old_path: None,
+    };
+
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            let (old_path, new_path) = (&event.paths[0], &event.paths[1]);
+            match (is_match(old_path), is_match(new_path)) {
+                (true, true) => Some(DirectoryChange {
+                    kind: DirectoryChangeKind::Renamed,
+                    path: new_path.to_string_lossy().to_string(),
+                    old_path: Some(old_path.to_string_lossy().to_string()),
+                }),
+                // Moved in or out of what the window is interested in
+                (true, false) => Some(change(DirectoryChangeKind::Removed, old_path)),
+                (false, true) => Some(change(DirectoryChangeKind::Created, new_path)),
+                (false, false) => None,
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            let path = event.paths.first().filter(|path| is_match(path))?;
+            Some(change(
+                if path.exists() {
+                    DirectoryChangeKind::Created
+                } else {
+                    DirectoryChangeKind::Removed
+                },
+                path,
+            ))
+        }
+        EventKind::Create(_) => event.paths.first().filter(|path| is_match(path)).map(|path| change(DirectoryChangeKind::Created, path)),
+        EventKind::Remove(_) => event.paths.first().filter(|path| is_match(path)).map(|path| change(DirectoryChangeKind::Removed, path)),
+        kind if is_modified(kind) => event.paths.first().filter(|path| is_match(path)).map(|path| change(DirectoryChangeKind::Modified, path)),
+        _ => None,
+    }
+}
+
+fn get_recursive_mode(recursive: bool) -> RecursiveMode {
+    if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    }
+}
+
+#[cfg(unix)]
+fn get_file_id(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(windows)]
+fn get_file_id(metadata: &std::fs::Metadata) -> Option<u64> {
+    metadata.created().ok().and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok()).map(|duration| duration.as_nanos() as u64)
 }
 
 fn is_modified(event_kind: EventKind) -> bool {
@@ -26,6 +26,18 @@ export const DEFAULT_GREP_REQUEST: Mp.GrepRequest = {
     case_sensitive: false,
     regexp: false,
     recursive: true,
+    no_ignore: false,
+    hidden: false,
+    max_depth: null,
+    multiline: false,
+    pcre2: false,
+    encoding: null,
+    max_results: 10000,
+    max_matches_per_file: null,
+    max_filesize: null,
+    search_binary: false,
+    before_context: 0,
+    after_context: 0,
 };
 export const DEFAULT_PREFERENCE: Mp.Preference = {
     indentSize: 4,
@@ -1,5 +1,5 @@
 import util from "./util";
-import { IPCBase } from "./ipc";
+import { FileArg, IPCBase } from "./ipc";
 
 const ipc = new IPCBase();
 
@@ -12,26 +12,35 @@ class Helper {
         const locale = args.locales.some((locale) => locale.toLowerCase().includes("ja")) ? "ja" : "en";
         window.lang = locale;
 
+        const [file, ...rest] = args.files;
+
         if (!this.initialized) {
             await ipc.invoke("prepare_menu", undefined);
             await ipc.invoke("listen_file_drop", dropTagetId);
+            await this.openFiles(rest);
         }
 
         this.initialized = true;
 
-        if (args.file?.file_path) {
-            this.startWatch(args.file.file_path);
+        if (file?.file_path) {
+            this.startWatch(file.file_path);
         }
 
         return {
             mode: args.grep ? "grep" : "editor",
-            filePath: args.file?.file_path,
-            content: args.file?.content,
+            filePath: file?.file_path,
+            content: file?.content,
             grep: args.grep,
-            startLine: args.file?.start_line ? { x: args.file?.start_line.column, y: args.file?.start_line.row } : undefined,
+            startLine: file?.start_line ? { x: file.start_line.column, y: file.start_line.row } : undefined,
             locale,
-            encoding: args.file?.encoding,
+            encoding: file?.encoding,
+            bom: file?.bom ?? false,
+            eol: file?.eol,
+            readonly: file?.readonly ?? false,
+            lossy: file ? util.isLossy(file) : false,
+            pipe: file?.pipe ?? false,
             restorePosition: args.restore_position,
+            pcre2: args.pcre2,
             appDataDir: args.app_data_dir,
         };
     };
@@ -44,6 +53,18 @@ class Helper {
         }
     };
 
+    showInfoMessage = async (message: string) => {
+        await ipc.invoke("message", { dialog_type: "message", message, kind: "info" });
+    };
+
+    showWarningMessage = async (message: string) => {
+        await ipc.invoke("message", { dialog_type: "message", message, kind: "warning" });
+    };
+
+    warnLossy = async () => {
+        await this.showWarningMessage("This file is binary or has bytes that cannot be decoded. It is opened read only because saving it would corrupt it.");
+    };
+
     openContextMenu = async (position: Mp.Position) => {
         await ipc.invoke("open_list_context_menu", position);
     };
@@ -52,9 +73,9 @@ class Helper {
         return await ipc.invoke("message", { dialog_type: "ask", message, kind: "warning", ok_label: buttons ? buttons[0] : "Yes", cancel_label: buttons ? buttons[1] : "No" });
     };
 
-    saveFile = async (fullPath: string, data: string, encoding: string) => {
+    saveFile = async (fullPath: string, data: string, textState: Mp.TextState) => {
         try {
-            await ipc.invoke("write_text_file", { fullPath, data, encoding });
+            await ipc.invoke("write_text_file", { fullPath, data, encoding: textState.encoding, bom: textState.bom, eol: textState.eol });
             return true;
         } catch (ex: any) {
             await this.showErrorMessage(ex);
@@ -62,6 +83,18 @@ class Helper {
         }
     };
 
+    setExitCode = async (code: number) => {
+        await ipc.invoke("set_exit_code", code);
+    };
+
+    writeStdout = async (data: string, textState: Mp.TextState) => {
+        try {
+            await ipc.invoke("write_stdout", { fullPath: "", data, encoding: textState.encoding, bom: textState.bom, eol: textState.eol });
+        } catch (ex: any) {
+            await this.showErrorMessage(ex);
+        }
+    };
+
     openFile = async () => {
         try {
             return await ipc.invoke("show_open_dialog", { dialog_type: "ask", message: "" });
@@ -77,6 +110,10 @@ class Helper {
                 file_path: filePath,
                 content: readResult.content,
                 encoding: readResult.encoding,
+                bom: readResult.bom,
+                eol: readResult.eol,
+                binary: readResult.binary,
+                invalid_ranges: readResult.invalid_ranges,
             };
         } catch (ex: any) {
             this.showErrorMessage(ex);
@@ -87,12 +124,32 @@ class Helper {
         await ipc.invoke("new_window", util.toCommandLineArgs(filePath, grepRequest, position));
     };
 
+    private openFiles = async (files: FileArg[]) => {
+        for (const file of files) {
+            const position = file.start_line ? { x: file.start_line.column, y: file.start_line.row } : undefined;
+            const args = util.toCommandLineArgs(file.file_path, undefined, position);
+            if (file.encoding) {
+                args.push("--encoding", file.encoding);
+            }
+            if (file.readonly) {
+                args.push("--readonly");
+            }
+            await ipc.invoke("new_window", args);
+        }
+    };
+
     startWatch = async (target: string) => {
         await this.abortWatch();
         this.watchTarget = target;
         ipc.invoke("watch", this.watchTarget);
     };
 
+    startFollow = async (target: string) => {
+        await this.abortWatch();
+        this.watchTarget = target;
+        ipc.invoke("follow", this.watchTarget);
+    };
+
     abortWatch = async () => {
         if (this.watchTarget) {
             await ipc.invoke("unwatch", this.watchTarget);
@@ -100,12 +157,19 @@ class Helper {
     };
 
     grep = async (request: Mp.GrepRequest) => {
-        const results = await ipc.invoke("run_grep", request);
-        return results.sort((a, b) => a.full_path.localeCompare(b.full_path) || a.line_number - b.line_number);
+        return await ipc.invoke("run_grep", request);
+    };
+
+    previewReplace = async (request: Mp.ReplaceRequest) => {
+        return await ipc.invoke("run_replace", request);
+    };
+
+    applyReplace = async (request: Mp.ReplaceRequest, files: Mp.ReplaceSelection[]) => {
+        return await ipc.invoke("apply_replace", { request, files });
     };
 
-    abortGrep = async () => {
-        await ipc.invoke("abort_grep", undefined);
+    abortGrep = async (jobId: number) => {
+        await ipc.invoke("abort_grep", jobId);
     };
 
     changeEncoding = async (filePath: string, encoding: string) => {
@@ -10,6 +10,8 @@ type WriteFileInfo = {
     fullPath: string;
     data: string;
     encoding?: string;
+    bom?: boolean;
+    eol?: Mp.Eol;
 };
 
 type DialogOptions = {
@@ -22,31 +24,44 @@ type DialogOptions = {
     default_path?: string;
 };
 
-type InitArgs = {
-    file?: {
-        file_path?: string;
-        content?: string;
-        encoding?: string;
-        start_line?: {
-            column: number;
-            row: number;
-        };
+export type FileArg = {
+    file_path?: string;
+    content?: string;
+    encoding?: string;
+    bom: boolean;
+    eol?: Mp.Eol;
+    binary: boolean;
+    invalid_ranges: [number, number][];
+    start_line?: {
+        column: number;
+        row: number;
     };
+    readonly: boolean;
+    pipe: boolean;
+};
+
+type InitArgs = {
+    files: FileArg[];
     grep?: Mp.GrepRequest;
     locales: string[];
     restore_position: boolean;
+    pcre2: boolean;
     app_data_dir: string;
 };
 
 type ReadResult = {
     content: string;
     encoding: string;
+    bom: boolean;
+    eol: Mp.Eol;
+    binary: boolean;
+    invalid_ranges: [number, number][];
 };
 
 type TauriCommandMap = {
     prepare_menu: TauriCommand<undefined, undefined>;
     open_list_context_menu: TauriCommand<Mp.Position, undefined>;
-    new_window: TauriCommand<string, undefined>;
+    new_window: TauriCommand<string[], undefined>;
     exists: TauriCommand<string, boolean>;
     is_uris_available: TauriCommand<undefined, boolean>;
     read_uris: TauriCommand<undefined, Mp.ClipboardData>;
@@ -56,8 +71,20 @@ type TauriCommandMap = {
     mkdir_all: TauriCommand<string, undefined>;
     create: TauriCommand<string, undefined>;
     read_text_file: TauriCommand<string, ReadResult>;
+    open_stream: TauriCommand<string, Mp.StreamInfo>;
+    read_chunk: TauriCommand<Mp.ChunkRequest, Mp.Chunk>;
+    close_stream: TauriCommand<number, undefined>;
+    build_line_index: TauriCommand<string, undefined>;
+    line_to_offset: TauriCommand<Mp.LineQuery, number>;
+    offset_to_line: TauriCommand<Mp.OffsetQuery, number>;
+    read_lines: TauriCommand<Mp.LineRangeRequest, Mp.LineRange>;
     write_text_file: TauriCommand<WriteFileInfo, undefined>;
+    write_stdout: TauriCommand<WriteFileInfo, undefined>;
+    set_exit_code: TauriCommand<number, undefined>;
     watch: TauriCommand<string, undefined>;
+    follow: TauriCommand<string, undefined>;
+    watch_directory: TauriCommand<Mp.DirectoryWatchRequest, undefined>;
+    unwatch_directory: TauriCommand<string, undefined>;
     unwatch: TauriCommand<string, undefined>;
     message: TauriCommand<DialogOptions, Mp.MessageResult>;
     show_open_dialog: TauriCommand<DialogOptions, Mp.OpenFileResult | null>;
@@ -67,8 +94,10 @@ type TauriCommandMap = {
     listen_file_drop: TauriCommand<string, undefined>;
     unlisten_file_drop: TauriCommand<undefined, undefined>;
     change_theme: TauriCommand<Mp.Theme, undefined>;
-    run_grep: TauriCommand<Mp.GrepRequest, Mp.GrepResult[]>;
-    abort_grep: TauriCommand<undefined, undefined>;
+    run_grep: TauriCommand<Mp.GrepRequest, number>;
+    abort_grep: TauriCommand<number, undefined>;
+    run_replace: TauriCommand<Mp.ReplaceRequest, Mp.ReplacePreview[]>;
+    apply_replace: TauriCommand<Mp.ApplyReplaceRequest, Mp.ReplaceOutcome[]>;
     is_file: TauriCommand<string, boolean>;
     change_encoding: TauriCommand<Mp.EncodeArg, string>;
 };
@@ -10,9 +10,16 @@ declare global {
         "after-toggle-maximize": Mp.SettingsChangeEvent;
         contextmenu_event: Mp.ContextMenuEvent;
         watch_event: Mp.WatchEvent;
+        watch_append: Mp.WatchAppendEvent;
+        watch_directory_event: Mp.DirectoryWatchEvent;
         watch_confirm_event: Mp.WatchConfirmEvent;
         grep_progress: Mp.GrepProgress;
+        grep_results: Mp.GrepBatch;
+        grep_complete: Mp.GrepSummary;
+        grep_start: Mp.AnyEvent;
+        grep_batch: Mp.AnyEvent;
         grep_end: Mp.AnyEvent;
+        line_index_event: Mp.LineIndexEvent;
         dialog: boolean;
         encoding_changed: Mp.AnyEvent;
         refelect_settings: Mp.AnyEvent;
@@ -43,9 +50,13 @@ declare global {
             | "searchHighlightBackground"
             | "link";
 
+        type Eol = "lf" | "crlf" | "cr" | "mixed";
+
         type TextState = {
             textType: Mp.TextType;
             encoding: string;
+            bom: boolean;
+            eol?: Mp.Eol;
         };
 
         type ContextMenuEvent = {
@@ -145,6 +156,10 @@ declare global {
             file_path: string;
             content: string;
             encoding: string;
+            bom: boolean;
+            eol: Mp.Eol;
+            binary: boolean;
+            invalid_ranges: [number, number][];
         };
 
         type PasteData = {
@@ -159,7 +174,13 @@ declare global {
             grep?: Mp.GrepRequest;
             locale: Mp.LocaleName;
             encoding?: string;
+            bom: boolean;
+            eol?: Mp.Eol;
+            readonly: boolean;
+            lossy: boolean;
+            pipe: boolean;
             restorePosition: boolean;
+            pcre2: boolean;
             appDataDir: string;
         };
 
@@ -181,10 +202,41 @@ declare global {
             doNotNotify: boolean;
         };
 
+        type WatchEventKind = "modified" | "removed" | "renamed" | "recreated";
+
         type WatchEvent = {
+            kind: Mp.WatchEventKind;
             file_path: string;
+            old_path?: string;
             content: string;
             encoding: string;
+            bom: boolean;
+            eol: Mp.Eol;
+            binary: boolean;
+            invalid_ranges: [number, number][];
+        };
+
+        type WatchAppendEvent = {
+            file_path: string;
+            content: string;
+        };
+
+        type DirectoryWatchRequest = {
+            path: string;
+            recursive: boolean;
+            include: string[];
+            exclude: string[];
+        };
+
+        type DirectoryChange = {
+            kind: "created" | "modified" | "removed" | "renamed";
+            path: string;
+            old_path?: string;
+        };
+
+        type DirectoryWatchEvent = {
+            directory: string;
+            changes: Mp.DirectoryChange[];
         };
 
         type GrepRequest = {
@@ -195,16 +247,110 @@ declare global {
             case_sensitive: boolean;
             regexp: boolean;
             recursive: boolean;
+            no_ignore: boolean;
+            hidden: boolean;
+            max_depth: number | null;
+            multiline: boolean;
+            pcre2: boolean;
+            encoding?: string | null;
+            max_results: number | null;
+            max_matches_per_file: number | null;
+            max_filesize: number | null;
+            search_binary: boolean;
+            before_context: number;
+            after_context: number;
         };
 
         type GrepResult = {
             full_path: string;
+            encoding: string;
             line_number: number;
             line: string;
+            /* Byte offsets in line */
             ranges: [number, number][];
+            /* UTF-16 offsets in line */
+            columns: [number, number][];
+            spans: GrepSpan[];
+            before: GrepContextLine[];
+            after: GrepContextLine[];
+        };
+
+        type GrepSpan = {
+            start: [number, number];
+            end: [number, number];
+            start_utf16: [number, number];
+            end_utf16: [number, number];
+        };
+
+        type GrepContextLine = {
+            line_number: number;
+            line: string;
+        };
+
+        type GrepBatch = {
+            job_id: number;
+            results: GrepResult[];
+        };
+
+        type GrepSummary = {
+            job_id: number;
+            total: number;
+            searched: number;
+            matches: number;
+            cancelled: boolean;
+            truncated: boolean;
+            truncated_files: string[];
+            skipped: SkippedFile[];
+            error: string | null;
+        };
+
+        type SkipReason = "too_large" | "binary" | "error";
+
+        type SkippedFile = {
+            full_path: string;
+            reason: SkipReason;
+        };
+
+        type ReplaceRequest = {
+            grep: GrepRequest;
+            replacement: string;
+        };
+
+        type ReplaceChange = {
+            line_number: number;
+            before: string;
+            after: string;
+        };
+
+        type ReplacePreview = {
+            full_path: string;
+            encoding: string;
+            hash: string;
+            changes: ReplaceChange[];
+        };
+
+        type ReplaceSelection = {
+            full_path: string;
+            hash: string;
+            line_numbers: number[];
+        };
+
+        type ApplyReplaceRequest = {
+            request: ReplaceRequest;
+            files: ReplaceSelection[];
+        };
+
+        type ReplaceStatus = "replaced" | "conflict" | "failed";
+
+        type ReplaceOutcome = {
+            full_path: string;
+            status: ReplaceStatus;
+            replaced: number;
+            message: string | null;
         };
 
         type GrepProgress = {
+            job_id: number;
             processing: string;
             current: number;
             total: number;
@@ -221,6 +367,57 @@ declare global {
             submenuId?: keyof Mp.MainContextMenuSubTypeMap;
         };
 
+        type StreamInfo = {
+            handle: number;
+            size: number;
+            encoding: string;
+            bom: boolean;
+            line_count: number;
+        };
+
+        type ChunkRequest = {
+            handle: number;
+            offset: number;
+            len: number;
+        };
+
+        type Chunk = {
+            content: string;
+            offset: number;
+            next_offset: number;
+            eof: boolean;
+        };
+
+        type LineIndexEvent = {
+            file_path: string;
+            line_count: number;
+            error?: string;
+        };
+
+        type LineQuery = {
+            file_path: string;
+            line: number;
+        };
+
+        type OffsetQuery = {
+            file_path: string;
+            offset: number;
+        };
+
+        type LineRangeRequest = {
+            file_path: string;
+            start_line: number;
+            count: number;
+        };
+
+        type LineRange = {
+            start_line: number;
+            end_line: number;
+            start_offset: number;
+            end_offset: number;
+            content: string;
+        };
+
         type EncodeArg = {
             content?: string;
             file_path?: string;
@@ -9,8 +9,12 @@ class Util {
         return navigator.userAgent.includes(OS.windows);
     }
 
-    toCommandLineArgs(filePath?: string, grepRequest?: Mp.GrepRequest, position?: Mp.Position): string {
-        const args = [filePath];
+    isLossy(result: { binary: boolean; invalid_ranges: [number, number][] }) {
+        return result.binary || result.invalid_ranges.length > 0;
+    }
+
+    toCommandLineArgs(filePath?: string, grepRequest?: Mp.GrepRequest, position?: Mp.Position): string[] {
+        const args = filePath ? [filePath] : [];
         if (grepRequest) {
             args.push("-g");
             args.push(grepRequest.condition);
@@ -28,15 +32,60 @@ class Util {
             if (grepRequest.recursive) {
                 args.push("-s");
             }
+            if (grepRequest.no_ignore) {
+                args.push("--no-ignore");
+            }
+            if (grepRequest.hidden) {
+                args.push("--hidden");
+            }
+            if (grepRequest.max_depth) {
+                args.push("--max-depth");
+                args.push(grepRequest.max_depth.toString());
+            }
+            if (grepRequest.multiline) {
+                args.push("-U");
+            }
+            if (grepRequest.pcre2) {
+                args.push("-P");
+            }
+            if (grepRequest.encoding) {
+                args.push("--encoding");
+                args.push(grepRequest.encoding);
+            }
+            if (grepRequest.max_results) {
+                args.push("--max-results");
+                args.push(grepRequest.max_results.toString());
+            }
+            if (grepRequest.max_matches_per_file) {
+                args.push("--max-count");
+                args.push(grepRequest.max_matches_per_file.toString());
+            }
+            if (grepRequest.max_filesize) {
+                args.push("--max-filesize");
+                args.push(grepRequest.max_filesize.toString());
+            }
+            if (grepRequest.search_binary) {
+                args.push("--binary");
+            }
+            if (grepRequest.before_context) {
+                args.push("-B");
+                args.push(grepRequest.before_context.toString());
+            }
+            if (grepRequest.after_context) {
+                args.push("-A");
+                args.push(grepRequest.after_context.toString());
+            }
         } else if (position) {
-            args.push(position.x.toString());
+            args.push("--line");
             args.push(position.y.toString());
+            args.push("--column");
+            args.push(position.x.toString());
         }
 
-        return args.join(" ");
+        return args;
     }
 
-    getFileMenubarItems(history: string[], encoding: string): Mp.MenuItem[] {
+    getFileMenubarItems(history: string[], encoding: string, follow: boolean): Mp.MenuItem[] {
         return [
             {
                 id: "New",
@@ -67,6 +116,12 @@ class Util {
                 type: "text",
                 accel: "Ctrl+P",
             },
+            {
+                id: "Follow",
+                label: "Follow",
+                type: "check",
+                checked: follow,
+            },
             {
                 type: "separator",
             },
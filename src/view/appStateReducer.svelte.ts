@@ -7,6 +7,11 @@ type CusorPosition = {
     column: number;
 };
 
+type ReplacePreviewState = {
+    request: Mp.ReplaceRequest;
+    previews: Mp.ReplacePreview[];
+};
+
 type AppState = {
     mode: Mp.Mode;
     fullPath: string;
@@ -18,18 +23,23 @@ type AppState = {
     visibleMenubarItem: string;
     showWatchDialog: boolean;
     watchThisFile: boolean;
-    suspendWatch: boolean;
+    follow: boolean;
     showGrepDialog: boolean;
     grepRequest: Mp.GrepRequest;
     grepResults: Mp.GrepResult[];
+    grepSummary: Mp.GrepSummary | undefined;
     showGrepProgress: boolean;
+    replacePreview: ReplacePreviewState | undefined;
     startLine: Mp.Position | undefined;
+    readonly: boolean;
+    pipe: boolean;
     columnSelection: boolean;
     cusorPosition: CusorPosition;
     lineEnding: string;
     language: string;
     hoverMenuItemGroup: string;
     showPreference: boolean;
+    pcre2Available: boolean;
 };
 
 export const initialAppState: AppState = {
@@ -43,21 +53,26 @@ export const initialAppState: AppState = {
     visibleMenubarItem: "",
     showWatchDialog: false,
     watchThisFile: true,
-    suspendWatch: false,
+    follow: false,
     showGrepDialog: false,
     grepRequest: DEFAULT_GREP_REQUEST,
     grepResults: [],
+    grepSummary: undefined,
     startLine: undefined,
+    readonly: false,
+    pipe: false,
     showGrepProgress: false,
+    replacePreview: undefined,
     columnSelection: false,
     cusorPosition: { line: 0, column: 0 },
     lineEnding: "CRLF",
     language: "",
     hoverMenuItemGroup: "",
     showPreference: false,
+    pcre2Available: false,
 };
 
-export const textState: Mp.TextState = $state({ textType: "plain", encoding: DEFAULT_ENCODING });
+export const textState: Mp.TextState = $state({ textType: "plain", encoding: DEFAULT_ENCODING, bom: false });
 export const settings: Mp.Settings = $state(defaultSettings);
 export const initSettings = (data: Mp.Settings) => {
     settings.bounds = data.bounds;
@@ -88,7 +103,7 @@ export const selectedPreference: SelectedPreferenceTab = $state({ tab: "view" })
 
 type AppAction =
     | { type: "mode"; value: Mp.Mode }
-    | { type: "init"; value: { filePath: string; content: string; mode: Mp.Mode; startLine?: Mp.Position } }
+    | { type: "init"; value: { filePath: string; content: string; mode: Mp.Mode; startLine?: Mp.Position; readonly: boolean; pipe: boolean } }
     | { type: "fullPath"; value: string }
     | { type: "content"; value: string }
     | { type: "isMaximized"; value: boolean }
@@ -97,18 +112,22 @@ type AppAction =
     | { type: "visibleMenubarItem"; value: string }
     | { type: "showWatchDialog"; value: boolean }
     | { type: "watchThisFile"; value: boolean }
-    | { type: "suspendWatch"; value: boolean }
+    | { type: "follow"; value: boolean }
     | { type: "showGrepDialog"; value: boolean }
     | { type: "grepRequest"; value: Mp.GrepRequest }
     | { type: "grepResult"; value: Mp.GrepResult[] }
+    | { type: "grepSummary"; value: Mp.GrepSummary | undefined }
     | { type: "showGrepProgress"; value: boolean }
+    | { type: "replacePreview"; value: ReplacePreviewState | undefined }
     | { type: "columnSelection"; value: boolean }
     | { type: "cusorPosition"; value: CusorPosition }
     | { type: "lineEnding"; value: string }
     | { type: "language"; value: string }
     | { type: "hoverMenuItemGroup"; value: string }
     | { type: "showPreference"; value: boolean }
-    | { type: "isFullScreen"; value: boolean };
+    | { type: "isFullScreen"; value: boolean }
+    | { type: "pcre2Available"; value: boolean }
+    | { type: "readonly"; value: boolean };
 
 const updater = (state: AppState, action: AppAction): AppState => {
     switch (action.type) {
@@ -116,7 +135,7 @@ const updater = (state: AppState, action: AppAction): AppState => {
             return { ...state, mode: action.value };
 
         case "init":
-            return { ...state, fullPath: action.value.filePath, content: action.value.content, mode: action.value.mode, startLine: action.value.startLine };
+            return { ...state, fullPath: action.value.filePath, content: action.value.content, mode: action.value.mode, startLine: action.value.startLine, readonly: action.value.readonly, pipe: action.value.pipe, follow: false };
 
         case "fullPath":
             if (action.value) {
@@ -143,8 +162,8 @@ const updater = (state: AppState, action: AppAction): AppState => {
         case "watchThisFile":
             return { ...state, watchThisFile: action.value };
 
-        case "suspendWatch":
-            return { ...state, suspendWatch: action.value };
+        case "follow":
+            return { ...state, follow: action.value };
 
         case "showGrepDialog":
             return { ...state, showGrepDialog: action.value };
@@ -155,9 +174,15 @@ const updater = (state: AppState, action: AppAction): AppState => {
         case "grepResult":
             return { ...state, grepResults: action.value };
 
+        case "grepSummary":
+            return { ...state, grepSummary: action.value };
+
         case "showGrepProgress":
             return { ...state, showGrepProgress: action.value };
 
+        case "replacePreview":
+            return { ...state, replacePreview: action.value };
+
         case "columnSelection":
             return { ...state, columnSelection: action.value };
 
@@ -182,6 +207,12 @@ const updater = (state: AppState, action: AppAction): AppState => {
         case "isFullScreen":
             return { ...state, isFullScreen: action.value };
 
+        case "pcre2Available":
+            return { ...state, pcre2Available: action.value };
+
+        case "readonly":
+            return { ...state, readonly: action.value };
+
         default:
             return state;
     }